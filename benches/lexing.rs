@@ -0,0 +1,43 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use simple_lexer::lexer::{ErrorHandler, Lexer, LexerOptions};
+
+// a bit of everything, with enough whitespace and
+// comments for skipping them to make a difference
+fn large_generated_source() -> String {
+    let statement = "let greeting = 'hello' + \"world\"; // greet\n\
+                     /* the answer */ let answer = (40 + 2) * 1.5;\n\
+                     if answer >= 42 { answer += 1; }\n";
+    statement.repeat(2_000)
+}
+
+fn bench_lexing_configurations(c: &mut Criterion) {
+    let source = large_generated_source();
+    let configurations = [
+        ("default", false, false),
+        ("skip_whitespace", true, false),
+        ("skip_comments", false, true),
+        ("skip_whitespace_and_comments", true, true),
+    ];
+
+    let mut group = c.benchmark_group("lexing");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+
+    for (name, skip_whitespace, skip_comments) in configurations {
+        group.bench_function(BenchmarkId::from_parameter(name), |b| {
+            b.iter(|| {
+                let mut handler = ErrorHandler::new();
+                let options = LexerOptions {
+                    skip_whitespace,
+                    skip_comments,
+                    ..LexerOptions::default()
+                };
+                Lexer::with_options(&source, &mut handler, options).into_tokens()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_lexing_configurations);
+criterion_main!(benches);
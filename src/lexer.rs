@@ -5,13 +5,27 @@
 
 mod character_helpers;
 mod token;
+mod token_helpers;
 
-use token::*;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::iter::Peekable;
+use std::path::Path;
+use std::str::CharIndices;
+
+pub use token::*;
+pub use token_helpers::*;
 
 #[derive(Debug, PartialEq)]
+#[allow(clippy::enum_variant_names)]
 enum StringState {
     InSingleQuote,
     InDoubleQuote,
+    InBacktick,
+    // holds the byte length of the opening delimiter,
+    // and the delimiter closing the custom string
+    InCustom { open_length: usize, close: char },
 }
 
 #[derive(Debug, PartialEq)]
@@ -21,12 +35,46 @@ enum State {
     InString(StringState),
     InIdentifier,
     InOperator,
+    InWhitespace,
+    InComment,
+    // holds the current nesting depth of the block comment
+    // and the maximum one it reached
+    InBlockComment { depth: usize, max_depth: usize },
+    // buffers a run of characters that don't start any token
+    InError,
 }
 
 #[derive(Debug, PartialEq)]
-enum LexerErrorKind {
-    InvalidToken,
+pub enum LexerErrorKind {
+    // holds the character that doesn't start any token
+    InvalidToken { found: char },
     InvalidOperator,
+    AdjacentStringLiterals,
+    UnterminatedComment,
+    StatementInExpression,
+    InvalidCharLiteral,
+    UnterminatedString,
+    // a string still open after max_string_length bytes,
+    // its closing quote is likely missing
+    SuspiciousStringLength,
+    // a number directly followed by letters, like `1abc`
+    InvalidNumberLiteral,
+    // a number that doesn't fit in an i64, or an f64 for decimals
+    NumberOutOfRange,
+    // a string that isn't quoted like the first one of its statement
+    MixedQuotes,
+    // an identifier with non ascii letters, when they're rejected
+    NonAsciiIdentifier,
+    // the error limit of the handler was reached,
+    // the rest of the input isn't lexed
+    TooManyErrors,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    // the source can still be lexed, but it's likely a mistake
+    Warning,
 }
 
 #[derive(Debug, PartialEq)]
@@ -35,48 +83,538 @@ pub struct LexerError {
     kind: LexerErrorKind,
 }
 
+#[derive(Default)]
 pub struct ErrorHandler {
     errors: Vec<LexerError>,
+    warnings: Vec<LexerError>,
+    // lexing stops once this many errors were collected
+    max_errors: Option<usize>,
+}
+
+#[derive(Debug)]
+pub struct LexerOptions {
+    // reports a warning for string literals that directly
+    // follow each other, like `"a" "b"`
+    pub forbid_adjacent_strings: bool,
+    // lexes double quoted text as an identifier instead of a string,
+    // like in SQL where `"column"` names a column
+    pub double_quoted_identifiers: bool,
+    // keeps `Invalid` tokens out of the token stream,
+    // their errors are still collected by the handler
+    pub drop_invalid_tokens: bool,
+    // maps each keyword to its kind, identifiers
+    // that aren't in the map are plain identifiers
+    pub keywords: HashMap<String, KeywordKind>,
+    // compares identifiers by their NFC form, so canonically
+    // equivalent identifiers are treated as the same name,
+    // combining marks are lexed as part of identifiers
+    #[cfg(feature = "unicode-normalization")]
+    pub normalize_identifiers: bool,
+    // operators longer than this are split, set it to 3
+    // to get operators like `===` or `>>=`, 0 is treated as 1
+    pub max_operator_length: usize,
+    // the character escaping the one following it in strings
+    pub escape_character: char,
+    // flags the token cut by the end of the input as incomplete,
+    // which is useful to know what's still being typed in an editor
+    pub mark_incomplete_tokens: bool,
+    // sets the line and column of the spans, it's
+    // off by default as it has a cost on every character
+    pub track_positions: bool,
+    // keeps whitespace tokens out of the token stream,
+    // the spans of the other tokens are unchanged
+    pub skip_whitespace: bool,
+    // keeps line and block comments out of the token stream
+    pub skip_comments: bool,
+    // lexes single quoted text as a char, which must
+    // hold exactly one character, like `'a'` or `'\n'`
+    pub char_literals: bool,
+    // a string longer than this, in bytes, is most likely missing its
+    // closing quote, so only its opening quote is reported and the
+    // text following it is lexed again instead of swallowing the input
+    pub max_string_length: Option<usize>,
+    // ends the token stream with an `Eof` token,
+    // a zero length token at the end of the input
+    pub emit_eof: bool,
+    // reports a number directly followed by an identifier, like `1abc`,
+    // as an invalid number literal instead of lexing them apart
+    pub forbid_identifiers_after_numbers: bool,
+    // lexes an `@` followed by an identifier as an attribute,
+    // like the `@override` decorator, a lone `@` stays invalid
+    pub attributes: bool,
+    // lets identifiers start with and contain `$`, like `$scope`
+    // or `foo$bar`, otherwise a `$` is an invalid token
+    pub allow_dollar_in_identifiers: bool,
+    // lexes each line break as a `Newline` token instead of whitespace,
+    // for languages where they end statements, `\r\n` is a single one
+    pub newline_tokens: bool,
+    // lexes `r"..."` as a raw string, where backslashes are literal,
+    // hashes like `r#"..."#` let it hold double quotes
+    pub raw_strings: bool,
+    // reports a warning for the numbers that can't be represented,
+    // integers must fit in an i64 and decimals in an f64
+    pub check_number_range: bool,
+    // reports a warning for the strings that aren't quoted like
+    // the first string of their statement, like `'a' + "b"`
+    pub warn_mixed_quotes: bool,
+    // the opening and closing delimiters of custom strings, like
+    // `«` and `»`, they're checked before the other tokens
+    pub string_delimiters: Vec<(char, char)>,
+    // reports the identifiers with non ascii letters, like `café`,
+    // as invalid tokens instead of accepting any letter
+    pub ascii_identifiers_only: bool,
+}
+
+/**
+ * Builds a lexer with some of its options changed,
+ * the other ones keep their default value
+ */
+#[derive(Debug, Default)]
+pub struct LexerBuilder {
+    options: LexerOptions,
+}
+
+/**
+ * The handler of a lexer, either lent by the caller
+ * or owned by the lexer itself
+ */
+enum LexerHandler<'a> {
+    Borrowed(&'a mut ErrorHandler),
+    Owned(ErrorHandler),
+}
+
+impl std::ops::Deref for LexerHandler<'_> {
+    type Target = ErrorHandler;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            LexerHandler::Borrowed(handler) => handler,
+            LexerHandler::Owned(handler) => handler,
+        }
+    }
+}
+
+impl std::ops::DerefMut for LexerHandler<'_> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            LexerHandler::Borrowed(handler) => handler,
+            LexerHandler::Owned(handler) => handler,
+        }
+    }
 }
 
 pub struct Lexer<'a> {
     current_state: State,
     // byte index of the first character of the token being buffered
     buffered_token_start: usize,
-    input: &'a String,
-    /**
-     * This is the index of the current character being processed
-     * in the vector of characters, not the byte index of the
-     * character in the input string
-     * If you want the byte index of the character in the input string
-     * use the current_code_point_byte_index value
-     */
-    cursor: usize,
+    input: &'a str,
+    // byte index of the character being processed in the input string
     current_character_byte_index: usize,
     tokens: Vec<Token>,
-    handler: &'a mut ErrorHandler,
+    // number of tokens at the front of self.tokens
+    // already handed out by next_token
+    read_tokens: usize,
+    handler: LexerHandler<'a>,
+    options: LexerOptions,
+    // byte index of the start of each line seen so far,
+    // only filled when positions are tracked
+    line_starts: Vec<usize>,
+    // TODO: could have a better data structure?
+    characters: Peekable<CharIndices<'a>>,
+    // byte index the characters start at, they're
+    // restarted there when the cursor is rewound
+    characters_offset: usize,
+    // the character the cursor is on and its byte index,
+    // None once the end of the input is reached
+    current_group: Option<(usize, char)>,
+    finished: bool,
+    // start of the last string literal, as long as only
+    // whitespace and comments followed it
+    previous_string_start: Option<usize>,
+    // kind of the first string literal of the statement being lexed
+    statement_string_kind: Option<StringKind>,
+    // end of the last token consumed, even if it was skipped,
+    // the next one can't start before it
+    last_token_end: usize,
+    // severities of the diagnostics reported since they were last
+    // streamed, in the order they were reported
+    // None when they aren't streamed
+    reported_severities: Option<Vec<Severity>>,
+}
+
+impl LexerErrorKind {
+    fn message(&self) -> &'static str {
+        match self {
+            LexerErrorKind::InvalidToken { .. } => "invalid token",
+            LexerErrorKind::InvalidOperator => "invalid operator",
+            LexerErrorKind::AdjacentStringLiterals => "adjacent string literals",
+            LexerErrorKind::UnterminatedComment => "unterminated block comment",
+            LexerErrorKind::StatementInExpression => "statement in an expression",
+            LexerErrorKind::InvalidCharLiteral => "invalid char literal",
+            LexerErrorKind::UnterminatedString => "unterminated string",
+            LexerErrorKind::SuspiciousStringLength => "suspiciously long string",
+            LexerErrorKind::InvalidNumberLiteral => "invalid number literal",
+            LexerErrorKind::NumberOutOfRange => "number out of range",
+            LexerErrorKind::MixedQuotes => "mixed quotes",
+            LexerErrorKind::NonAsciiIdentifier => "non ascii identifier",
+            LexerErrorKind::TooManyErrors => "too many errors, lexing stopped",
+        }
+    }
+
+    pub fn severity(&self) -> Severity {
+        match self {
+            LexerErrorKind::AdjacentStringLiterals
+            | LexerErrorKind::SuspiciousStringLength
+            | LexerErrorKind::NumberOutOfRange
+            | LexerErrorKind::MixedQuotes => Severity::Warning,
+            _ => Severity::Error,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+impl fmt::Display for LexerErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl fmt::Display for LexerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let end = self.span.end();
+        write!(f, "{} at bytes {}..{}", self.kind, self.span.start, end)
+    }
+}
+
+impl std::error::Error for LexerError {}
+
+impl LexerError {
+    pub fn span(&self) -> &Span {
+        &self.span
+    }
+
+    pub fn kind(&self) -> &LexerErrorKind {
+        &self.kind
+    }
+
+    pub fn severity(&self) -> Severity {
+        self.kind.severity()
+    }
+
+    /**
+     * Gets the line the error is on, and a line with
+     * carets under the erroneous part
+     */
+    pub fn snippet(&self, source: &str) -> String {
+        let (line_text, carets) = self.snippet_lines(source);
+
+        format!("{line_text}\n{carets}")
+    }
+
+    /**
+     * Renders the error with its position and the line
+     * it's on, with carets under the erroneous part
+     */
+    pub fn render(&self, source: &str) -> String {
+        let (line, column) = self.span.line_col(source);
+        let (line_text, carets) = self.snippet_lines(source);
+
+        let line_number = line.to_string();
+        let gutter = " ".repeat(line_number.len());
+
+        format!(
+            "{}: {} at {}:{}\n{} |\n{} | {}\n{} | {}",
+            self.severity(),
+            self.kind.message(),
+            line,
+            column,
+            gutter,
+            line_number,
+            line_text,
+            gutter,
+            carets,
+        )
+    }
+
+    fn snippet_lines<'s>(&self, source: &'s str) -> (&'s str, String) {
+        let (_, column) = self.span.line_col(source);
+
+        let line_start = source[..self.span.start]
+            .rfind(['\n', '\r'])
+            .map_or(0, |newline_index| newline_index + 1);
+        let line_end = source[self.span.start..]
+            .find(['\n', '\r'])
+            .map_or(source.len(), |newline_index| {
+                self.span.start + newline_index
+            });
+
+        // the carets stop at the end of the line for multiline spans,
+        // and there's still one for errors at the end of the input
+        let span_end = self.span.end().min(line_end);
+        let caret_count = source[self.span.start..span_end].chars().count().max(1);
+        let carets = format!("{}{}", " ".repeat(column - 1), "^".repeat(caret_count));
+
+        (&source[line_start..line_end], carets)
+    }
 }
 
 impl ErrorHandler {
     pub fn new() -> Self {
-        Self { errors: Vec::new() }
+        Self {
+            errors: Vec::new(),
+            warnings: Vec::new(),
+            max_errors: None,
+        }
+    }
+
+    /**
+     * Creates a handler that stops the lexing once max_errors errors
+     * were collected, a TooManyErrors error is added at that point
+     */
+    pub fn with_max_errors(max_errors: usize) -> Self {
+        Self {
+            max_errors: Some(max_errors),
+            ..Self::new()
+        }
+    }
+
+    fn reached_max_errors(&self) -> bool {
+        self.max_errors
+            .is_some_and(|max_errors| self.errors.len() >= max_errors)
+    }
+
+    fn clear(&mut self) {
+        self.errors.clear();
+        self.warnings.clear();
+    }
+
+    // drops the diagnostics of the input from byte_offset on,
+    // it's about to be lexed again
+    fn clear_from(&mut self, byte_offset: usize) {
+        self.errors.retain(|error| error.span.start < byte_offset);
+        self.warnings
+            .retain(|warning| warning.span.start < byte_offset);
+    }
+
+    /**
+     * Gets the errors collected while lexing
+     *
+     * ```
+     * use simple_lexer::lexer::{ErrorHandler, Lexer, LexerErrorKind};
+     *
+     * let mut handler = ErrorHandler::new();
+     * let mut lexer = Lexer::new("let value =+ 1;", &mut handler);
+     * lexer.lex();
+     *
+     * assert!(handler.has_errors());
+     * let error = &handler.errors()[0];
+     * assert_eq!(error.kind(), &LexerErrorKind::InvalidOperator);
+     * assert_eq!((error.span().start, error.span().length), (10, 2));
+     * ```
+     */
+    pub fn errors(&self) -> &[LexerError] {
+        &self.errors
+    }
+
+    pub fn has_errors(&self) -> bool {
+        !self.errors.is_empty()
+    }
+
+    pub fn warnings(&self) -> &[LexerError] {
+        &self.warnings
+    }
+
+    pub fn errors_with_severity(&self, severity: Severity) -> &[LexerError] {
+        match severity {
+            Severity::Error => &self.errors,
+            Severity::Warning => &self.warnings,
+        }
+    }
+
+    // errors and warnings are kept apart, so a warning
+    // doesn't make has_errors true
+    fn add(&mut self, diagnostic: LexerError) {
+        match diagnostic.severity() {
+            Severity::Error => self.errors.push(diagnostic),
+            Severity::Warning => self.warnings.push(diagnostic),
+        }
+    }
+}
+
+impl Default for LexerOptions {
+    fn default() -> Self {
+        Self {
+            forbid_adjacent_strings: false,
+            double_quoted_identifiers: false,
+            drop_invalid_tokens: false,
+            keywords: token::default_keywords(),
+            #[cfg(feature = "unicode-normalization")]
+            normalize_identifiers: false,
+            max_operator_length: 2,
+            escape_character: '\\',
+            mark_incomplete_tokens: false,
+            track_positions: false,
+            skip_whitespace: false,
+            skip_comments: false,
+            char_literals: false,
+            max_string_length: None,
+            emit_eof: false,
+            forbid_identifiers_after_numbers: false,
+            attributes: false,
+            allow_dollar_in_identifiers: false,
+            newline_tokens: false,
+            raw_strings: false,
+            check_number_range: false,
+            warn_mixed_quotes: false,
+            string_delimiters: Vec::new(),
+            ascii_identifiers_only: false,
+        }
+    }
+}
+
+impl LexerBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keywords(mut self, keywords: HashMap<String, KeywordKind>) -> Self {
+        self.options.keywords = keywords;
+        self
+    }
+
+    pub fn skip_whitespace(mut self, skip_whitespace: bool) -> Self {
+        self.options.skip_whitespace = skip_whitespace;
+        self
+    }
+
+    pub fn skip_comments(mut self, skip_comments: bool) -> Self {
+        self.options.skip_comments = skip_comments;
+        self
+    }
+
+    pub fn attributes(mut self, attributes: bool) -> Self {
+        self.options.attributes = attributes;
+        self
+    }
+
+    pub fn max_operator_length(mut self, max_operator_length: usize) -> Self {
+        self.options.max_operator_length = max_operator_length;
+        self
+    }
+
+    pub fn emit_eof(mut self, emit_eof: bool) -> Self {
+        self.options.emit_eof = emit_eof;
+        self
     }
 
-    fn add_error(&mut self, error: LexerError) {
-        self.errors.push(error);
+    /**
+     * Registers a pair of delimiters for custom strings,
+     * it can be called once for each pair
+     */
+    pub fn string_delimiter(mut self, open: char, close: char) -> Self {
+        self.options.string_delimiters.push((open, close));
+        self
+    }
+
+    pub fn build<'a>(self, source: &'a str, handler: &'a mut ErrorHandler) -> Lexer<'a> {
+        Lexer::with_options(source, handler, self.options)
     }
 }
 
 impl<'a> Lexer<'a> {
-    pub fn new(source: &'a String, handler: &'a mut ErrorHandler) -> Self {
+    pub fn builder() -> LexerBuilder {
+        LexerBuilder::new()
+    }
+
+    pub fn new(source: &'a str, handler: &'a mut ErrorHandler) -> Self {
+        Self::with_options(source, handler, LexerOptions::default())
+    }
+
+    /**
+     * Creates a lexer over UTF-8 encoded bytes, the bytes are
+     * validated up front and lexed like a string slice
+     */
+    pub fn from_bytes(
+        bytes: &'a [u8],
+        handler: &'a mut ErrorHandler,
+    ) -> Result<Self, std::str::Utf8Error> {
+        let source = std::str::from_utf8(bytes)?;
+
+        Ok(Self::new(source, handler))
+    }
+
+    /**
+     * Creates a lexer recognizing only the given keywords instead of
+     * the default ones, they're all custom keywords of the `Other` category
+     * Set the keywords of the options to give them a kind
+     */
+    pub fn with_keywords(
+        source: &'a str,
+        handler: &'a mut ErrorHandler,
+        keywords: &[&str],
+    ) -> Self {
+        let options = LexerOptions {
+            keywords: keywords
+                .iter()
+                .map(|keyword| {
+                    let keyword_kind = KeywordKind::Custom(KeywordCategory::Other);
+                    (String::from(*keyword), keyword_kind)
+                })
+                .collect(),
+            ..LexerOptions::default()
+        };
+
+        Self::with_options(source, handler, options)
+    }
+
+    /**
+     * Creates a lexer that owns its handler, the errors
+     * and warnings are read through the handler method
+     */
+    pub fn owned(source: &'a str) -> Self {
+        let handler = LexerHandler::Owned(ErrorHandler::new());
+
+        Self::with_handler(source, handler, LexerOptions::default())
+    }
+
+    pub fn with_options(
+        source: &'a str,
+        handler: &'a mut ErrorHandler,
+        options: LexerOptions,
+    ) -> Self {
+        Self::with_handler(source, LexerHandler::Borrowed(handler), options)
+    }
+
+    fn with_handler(source: &'a str, handler: LexerHandler<'a>, options: LexerOptions) -> Self {
+        let mut characters = source.char_indices().peekable();
+        let current_group = characters.next();
+
         Self {
             current_state: State::Start,
             buffered_token_start: 0,
             current_character_byte_index: 0,
             input: source,
-            cursor: 0,
             tokens: Vec::new(),
+            read_tokens: 0,
             handler,
+            options,
+            line_starts: vec![0],
+            characters,
+            characters_offset: 0,
+            current_group,
+            finished: false,
+            previous_string_start: None,
+            statement_string_kind: None,
+            last_token_end: 0,
+            reported_severities: None,
         }
     }
 }
@@ -96,9 +634,13 @@ impl Lexer<'_> {
     fn handle_start(&mut self, character: char) {
         self.buffered_token_start = self.current_character_byte_index;
 
-        if character_helpers::is_digit(character) {
+        if self.buffered_token_start == 0 && self.input.starts_with("#!") {
+            self.handle_shebang();
+        } else if character_helpers::is_digit(character) {
             self.change_state(State::InNumber);
-        } else if character_helpers::is_letter(character) {
+        } else if let Some(hash_count) = self.raw_string_hash_count(character) {
+            self.handle_raw_string(hash_count);
+        } else if self.is_identifier_start(character) {
             self.change_state(State::InIdentifier);
         } else if character_helpers::is_single_quote(character) {
             // don't buffer the opening quote
@@ -108,6 +650,25 @@ impl Lexer<'_> {
             // don't buffer the opening quote
             self.advance_cursor();
             self.change_state(State::InString(StringState::InDoubleQuote));
+        } else if character_helpers::is_backtick(character) {
+            // don't buffer the opening backtick
+            self.advance_cursor();
+            self.change_state(State::InString(StringState::InBacktick));
+        } else if let Some(close) = self.custom_string_close(character) {
+            self.advance_cursor();
+            self.change_state(State::InString(StringState::InCustom {
+                open_length: character.len_utf8(),
+                close,
+            }));
+        } else if self.is_line_comment_start(character) {
+            self.change_state(State::InComment);
+        } else if self.is_block_comment_start(character) {
+            // the handler will take care of the opening delimiter
+            // and bump the depth to 1
+            self.change_state(State::InBlockComment {
+                depth: 0,
+                max_depth: 0,
+            });
         } else if character_helpers::is_operator(character) {
             self.change_state(State::InOperator);
         } else if character_helpers::is_semicolon(character) {
@@ -117,31 +678,267 @@ impl Lexer<'_> {
             // skip to the next character in the next iteration
             // of the state machine
             self.advance_cursor();
-        } else if character_helpers::is_whitespace(character) {
-            let token = token::create_token(TokenKind::Whitespace, self.buffered_token_start, 1);
+        } else if character_helpers::is_comma(character) {
+            let token = token::create_token(TokenKind::Comma, self.buffered_token_start, 1);
+            self.consume_token_explicit(token);
+            self.advance_cursor();
+        } else if character_helpers::is_colon(character) {
+            let token = token::create_token(TokenKind::Colon, self.buffered_token_start, 1);
+            self.consume_token_explicit(token);
+            self.advance_cursor();
+        } else if character_helpers::is_dot(character) {
+            self.handle_dot();
+        } else if character_helpers::is_question_mark(character) {
+            self.handle_question_mark();
+        } else if self.is_attribute_start(character) {
+            self.handle_attribute();
+        } else if character_helpers::is_delimiter(character) {
+            let token_kind = token::match_delimiter_to_token_kind(character);
+            let token = token::create_token(token_kind, self.buffered_token_start, 1);
 
             self.consume_token_explicit(token);
             self.advance_cursor();
+        } else if self.is_newline_token_start(character) {
+            self.handle_newline(character);
+        } else if character_helpers::is_whitespace(character) && self.options.skip_whitespace {
+            // no need to buffer whitespace that won't be a token
+            self.advance_cursor();
+        } else if character_helpers::is_whitespace(character) {
+            self.change_state(State::InWhitespace);
         } else {
-            // TODO: should I introduce an InError state
-            // so its the state handler will take responsibility
-            // on how to handle the errors?
-            // meh idk 😅, I'll just handle it here for now
-            let token = token::create_token(TokenKind::Invalid, self.buffered_token_start, 1);
+            self.change_state(State::InError);
+        }
+    }
 
-            self.consume_token_explicit(token);
+    /**
+     * Consumes the first line as a shebang, the
+     * line break that ends it isn't part of it
+     */
+    fn handle_shebang(&mut self) {
+        while self.current_group.is_some_and(|(_, character)| {
+            !character_helpers::is_newline(character)
+                && !character_helpers::is_carriage_return(character)
+        }) {
             self.advance_cursor();
+        }
 
-            self.handler.add_error(LexerError {
-                span: self.create_current_token_span(),
-                kind: LexerErrorKind::InvalidToken,
-            });
+        let shebang_length = self
+            .current_group
+            .map_or(self.input.len(), |(byte_index, _)| byte_index);
+        let token = token::create_token(TokenKind::Shebang, 0, shebang_length);
+        self.consume_token_explicit(token);
+    }
+
+    /**
+     * Consumes a raw string at once, it ends at the first double
+     * quote followed by as many hashes as the opening one
+     */
+    fn handle_raw_string(&mut self, hash_count: usize) {
+        // the content starts after the `r`, the hashes and the quote
+        let content_start = self.buffered_token_start + hash_count + 2;
+        let closing_delimiter = format!("\"{}", "#".repeat(hash_count));
+
+        let raw_string_end = match self.input[content_start..].find(&closing_delimiter) {
+            Some(content_length) => content_start + content_length + closing_delimiter.len(),
+            None => {
+                self.report(LexerError {
+                    span: Span::new(
+                        self.buffered_token_start,
+                        self.input.len() - self.buffered_token_start,
+                    ),
+                    kind: LexerErrorKind::UnterminatedString,
+                });
+                self.input.len()
+            }
+        };
+        while self
+            .current_group
+            .is_some_and(|(byte_index, _)| byte_index < raw_string_end)
+        {
+            self.advance_cursor();
+        }
+
+        let token = token::create_token(
+            TokenKind::String(StringKind::Raw),
+            self.buffered_token_start,
+            raw_string_end - self.buffered_token_start,
+        );
+        self.consume_token_explicit(token);
+    }
+
+    /**
+     * Consumes a line break as a newline token,
+     * along with the `\n` of a `\r\n`
+     */
+    fn handle_newline(&mut self, character: char) {
+        let newline_length = if character_helpers::is_carriage_return(character)
+            && self
+                .peek_next_char()
+                .is_some_and(character_helpers::is_newline)
+        {
+            2
+        } else {
+            1
+        };
+        let token = token::create_token(
+            TokenKind::Newline,
+            self.buffered_token_start,
+            newline_length,
+        );
+
+        self.consume_token_explicit(token);
+        for _ in 0..newline_length {
+            self.advance_cursor();
+        }
+    }
+
+    /**
+     * Checks if the character being processed is the `@`
+     * of an attribute, the name must follow it directly
+     */
+    fn is_attribute_start(&self, character: char) -> bool {
+        self.options.attributes
+            && character_helpers::is_at_sign(character)
+            && self
+                .peek_next_char()
+                .is_some_and(|character| self.is_identifier_start(character))
+    }
+
+    /**
+     * Consumes an `@` and the name following it as an attribute
+     */
+    fn handle_attribute(&mut self) {
+        // skip the `@`, the name is all identifier characters
+        self.advance_cursor();
+        while self
+            .current_group
+            .is_some_and(|(_, character)| self.is_in_identifier(character))
+        {
+            self.advance_cursor();
+        }
+
+        let attribute_end = self
+            .current_group
+            .map_or(self.input.len(), |(byte_index, _)| byte_index);
+        let token = token::create_token(
+            TokenKind::Attribute,
+            self.buffered_token_start,
+            attribute_end - self.buffered_token_start,
+        );
+        self.consume_token_explicit(token);
+    }
+
+    /**
+     * Consumes a `?` as a question mark token, or as the start
+     * of a `??` or `?.` operator
+     * They're looked up here rather than in the operator state
+     * since a lone `?` isn't an operator
+     */
+    fn handle_question_mark(&mut self) {
+        let mut characters = self.input[self.current_character_byte_index + 1..].chars();
+        let next_character = characters.next();
+        // `a?.5:1` is a ternary, not an optional chaining
+        let is_optional_chaining = next_character.is_some_and(character_helpers::is_dot)
+            && !characters.next().is_some_and(character_helpers::is_digit);
+
+        let token = if next_character.is_some_and(character_helpers::is_question_mark)
+            || is_optional_chaining
+        {
+            let operator = &self.input[self.buffered_token_start..self.buffered_token_start + 2];
+            let operator_kind = token::match_operator_slice_to_operator_kind(operator);
+            // the second character is part of the token too
+            self.advance_cursor();
+            token::create_token(
+                TokenKind::Operator(operator_kind),
+                self.buffered_token_start,
+                2,
+            )
+        } else {
+            token::create_token(TokenKind::Question, self.buffered_token_start, 1)
+        };
+
+        self.consume_token_explicit(token);
+        self.advance_cursor();
+    }
+
+    /**
+     * Consumes a `.` as a dot token, or as the start of a `..`
+     * or `...` range operator
+     * A dot inside a number is its decimal point, so this
+     * is only reached by the dots outside of numbers
+     */
+    fn handle_dot(&mut self) {
+        let dot_count = self.input[self.current_character_byte_index..]
+            .chars()
+            .take(3)
+            .take_while(|&character| character_helpers::is_dot(character))
+            .count();
+
+        let token_kind = if dot_count == 1 {
+            TokenKind::Dot
+        } else {
+            let operator_end = self.buffered_token_start + dot_count;
+            let operator = &self.input[self.buffered_token_start..operator_end];
+            TokenKind::Operator(token::match_operator_slice_to_operator_kind(operator))
+        };
+        let token = token::create_token(token_kind, self.buffered_token_start, dot_count);
+
+        self.consume_token_explicit(token);
+        for _ in 0..dot_count {
+            self.advance_cursor();
+        }
+    }
+
+    fn handle_in_error(&mut self, character: char) {
+        // consecutive invalid characters make a single invalid token
+        if self.is_token_start(character) {
+            self.consume_buffered_token();
+            self.reset_state();
+        } else {
+            self.advance_cursor();
         }
     }
 
     fn handle_in_number(&mut self, character: char) {
-        if character_helpers::is_digit(character) {
+        // a dot is the decimal point only when it's the first one
+        // of the number and it's followed by a digit, so `1.5` is
+        // a number while `1.field` is a number, a dot and an identifier
+        // It's also why `1..2` ends the number at the first dot of the range
+        let is_decimal_point = character_helpers::is_dot(character)
+            && !self.get_buffered_token().contains('.')
+            && self
+                .peek_next_char()
+                .is_some_and(character_helpers::is_digit);
+
+        if character_helpers::is_digit(character) || is_decimal_point {
             self.advance_cursor();
+        } else if self.options.forbid_identifiers_after_numbers
+            && self.is_identifier_start(character)
+        {
+            // the letters are part of the invalid literal
+            while self
+                .current_group
+                .is_some_and(|(_, character)| self.is_in_identifier(character))
+            {
+                self.advance_cursor();
+            }
+            self.current_character_byte_index = self
+                .current_group
+                .map_or(self.input.len(), |(byte_index, _)| byte_index);
+
+            self.report(LexerError {
+                span: self.create_current_token_span(),
+                kind: LexerErrorKind::InvalidNumberLiteral,
+            });
+            if !self.options.drop_invalid_tokens {
+                let token = token::create_token(
+                    TokenKind::Invalid,
+                    self.buffered_token_start,
+                    self.buffered_token_length(),
+                );
+                self.consume_token_explicit(token);
+            }
+            self.reset_state();
         } else {
             self.consume_buffered_token();
             self.reset_state();
@@ -149,10 +946,18 @@ impl Lexer<'_> {
     }
 
     fn handle_in_operator(&mut self, character: char) {
-        // operators can be at most 2 characters long
-        // len < 2 because the token's buffer is gonna grow by 1
-        // in this code path
-        if character_helpers::is_operator(character) && self.get_buffered_token().len() < 2 {
+        // operators can be at most max_operator_length characters long
+        // len < max_operator_length because the token's buffer is gonna
+        // grow by 1 in this code path
+        // an operator is at least 1 character long, even when the max is 0
+        // a comment right after an operator ends the operator,
+        // so does the opening delimiter of a custom string
+        if character_helpers::is_operator(character)
+            && self.buffered_token_length() < self.options.max_operator_length.max(1)
+            && !self.is_line_comment_start(character)
+            && !self.is_block_comment_start(character)
+            && self.custom_string_close(character).is_none()
+        {
             self.advance_cursor();
         } else {
             self.consume_buffered_token();
@@ -161,10 +966,17 @@ impl Lexer<'_> {
     }
 
     fn handle_in_string(&mut self, character: char) {
-        let is_closing_quote = if let State::InString(string_state) = &self.current_state {
+        // the buffered token holds the opening quote,
+        // a custom one may be more than a byte long
+        let (is_closing_quote, opening_quote_length) = if let State::InString(string_state) =
+            &self.current_state
+        {
             match string_state {
-                StringState::InSingleQuote => character_helpers::is_single_quote,
-                StringState::InDoubleQuote => character_helpers::is_double_quote,
+                StringState::InSingleQuote => (character_helpers::is_single_quote(character), 1),
+                StringState::InDoubleQuote => (character_helpers::is_double_quote(character), 1),
+                // interpolations like `${x}` are part of the string
+                StringState::InBacktick => (character_helpers::is_backtick(character), 1),
+                StringState::InCustom { open_length, close } => (character == *close, *open_length),
             }
         } else {
             // if this handler is called, the current state
@@ -173,80 +985,456 @@ impl Lexer<'_> {
             unreachable!();
         };
 
-        if !is_closing_quote(character) {
+        if self.options.max_string_length.is_some_and(|max_length| {
+            self.buffered_token_length() - opening_quote_length > max_length
+        }) {
+            self.report(LexerError {
+                span: Span::new(self.buffered_token_start, opening_quote_length),
+                kind: LexerErrorKind::SuspiciousStringLength,
+            });
+            if !self.options.drop_invalid_tokens {
+                let token = token::create_token(
+                    TokenKind::Invalid,
+                    self.buffered_token_start,
+                    opening_quote_length,
+                );
+                self.consume_token_explicit(token);
+            }
+
+            self.rewind_cursor(self.buffered_token_start + opening_quote_length);
+            self.reset_state();
+        } else if character == self.options.escape_character {
+            // skip the escaped character, so an escaped
+            // quote doesn't close the string
+            self.advance_cursor();
+            self.advance_cursor();
+        } else if !is_closing_quote {
             self.advance_cursor();
         } else {
             // don't reprocess the closing quote character
             self.advance_cursor();
 
+            // advance the character byte index so that the closing
+            // quote is included in the buffered token
+            // this isn't done when consuming the token, as a string
+            // consumed at the end of the input has no closing quote
+            self.current_character_byte_index += character.len_utf8();
+
             self.consume_buffered_token();
             self.reset_state();
         }
     }
 
-    fn handle_in_identifier(&mut self, character: char) {
-        if character_helpers::is_in_identifier(character) {
+    fn handle_in_whitespace(&mut self, character: char) {
+        if character_helpers::is_whitespace(character) && !self.is_newline_token_start(character) {
             self.advance_cursor();
         } else {
-            // Consuming of keywords is hidden under this function
-            // Something is an Identifier unless that
-            // identifier matches a keyword
             self.consume_buffered_token();
             self.reset_state();
         }
     }
-}
-
-// lexer utilities
-impl<'a> Lexer<'a> {
-    pub fn lex(&'a mut self) -> &'a Vec<self::Token> {
-        // TODO: could have a better data structure?
-        let mut characters = self.input.char_indices().peekable();
-
-        let mut advancement = 0;
-        let mut current_group = characters.next();
-
-        while current_group.is_some() {
-            let (current_character_byte_index, current_character) =
-                current_group.expect("This should never be None");
-            self.current_character_byte_index = current_character_byte_index;
-
-            match self.current_state {
-                State::Start => self.handle_start(current_character),
-                State::InIdentifier => self.handle_in_identifier(current_character),
-                State::InString(_) => self.handle_in_string(current_character),
-                State::InNumber => self.handle_in_number(current_character),
-                State::InOperator => self.handle_in_operator(current_character),
-            }
-
-            let delta = self.cursor - advancement;
-            for _ in 0..delta {
-                current_group = characters.next();
-                advancement += 1;
-            }
-        }
 
-        // consume the last buffered token
-        // if the state machine is still in a non-start state
-        if self.current_state != State::Start {
-            // advance the character index so that the last
-            // character is included in the buffered token
-            self.current_character_byte_index = self.input.len();
-            self.consume_buffered_token()
+    fn handle_in_comment(&mut self, character: char) {
+        // a lone `\r` is a line break too, and the `\r` of
+        // a `\r\n` is the start of the line break
+        if !character_helpers::is_newline(character)
+            && !character_helpers::is_carriage_return(character)
+        {
+            self.advance_cursor();
+        } else {
+            // the line break isn't part of the comment,
+            // it's reprocessed from the start state
+            self.consume_buffered_token();
+            self.reset_state();
         }
-
-        &self.tokens
     }
 
-    /**
-     * Creates a span from buffered_token_start, which is the
-     * byte index of the first character of the token being buffered
+    fn handle_in_block_comment(&mut self, character: char) {
+        let (depth, max_depth) =
+            if let State::InBlockComment { depth, max_depth } = self.current_state {
+                (depth, max_depth)
+            } else {
+                unreachable!();
+            };
+        let next_character = self.peek_next_char();
+
+        if self.is_block_comment_start(character) {
+            // skip both characters of the opening delimiter
+            // so `/*/` doesn't open and close the comment
+            self.advance_cursor();
+            self.advance_cursor();
+            self.change_state(State::InBlockComment {
+                depth: depth + 1,
+                max_depth: max_depth.max(depth + 1),
+            });
+        } else if character_helpers::is_asterisk(character)
+            && next_character.is_some_and(character_helpers::is_slash)
+        {
+            self.advance_cursor();
+            self.advance_cursor();
+
+            if depth > 1 {
+                self.change_state(State::InBlockComment {
+                    depth: depth - 1,
+                    max_depth,
+                });
+            } else {
+                // advance the character byte index so that the closing
+                // delimiter is included in the buffered token
+                self.current_character_byte_index += 2;
+
+                self.consume_buffered_token();
+                self.reset_state();
+            }
+        } else {
+            self.advance_cursor();
+        }
+    }
+
+    fn handle_in_identifier(&mut self, character: char) {
+        if self.is_in_identifier(character) {
+            self.advance_cursor();
+        } else {
+            // Consuming of keywords is hidden under this function
+            // Something is an Identifier unless that
+            // identifier matches a keyword
+            self.consume_buffered_token();
+            self.reset_state();
+        }
+    }
+}
+
+// lexer utilities
+impl<'a> Lexer<'a> {
+    pub fn lex(&mut self) -> &Vec<self::Token> {
+        self.run();
+
+        &self.tokens
+    }
+
+    /**
+     * Gets the handler collecting the errors and warnings of the lexer
+     */
+    pub fn handler(&self) -> &ErrorHandler {
+        &self.handler
+    }
+
+    /**
+     * Points the lexer at a new source, so it can be reused
+     * without reallocating its tokens
+     * The errors and warnings of the previous source are cleared,
+     * since their spans are meaningless in the new one
+     */
+    pub fn reset(&mut self, source: &'a str) {
+        self.input = source;
+        self.tokens.clear();
+        self.read_tokens = 0;
+        self.handler.clear();
+        self.reset_state();
+        self.buffered_token_start = 0;
+        self.current_character_byte_index = 0;
+        self.line_starts.truncate(1);
+        self.characters = source.char_indices().peekable();
+        self.characters_offset = 0;
+        self.current_group = self.characters.next();
+        self.finished = false;
+        self.previous_string_start = None;
+        self.statement_string_kind = None;
+        self.last_token_end = 0;
+    }
+
+    /**
+     * Lexes the input again starting from byte_offset, which must be
+     * a token boundary, the tokens before it are dropped
+     * The diagnostics from byte_offset on are replaced by the new ones
+     * Their spans are still relative to the start of the input
+     * Only the input the lexer was created with is lexed again,
+     * use lex_edited_from once it was edited
+     */
+    pub fn lex_from(&mut self, byte_offset: usize) -> &Vec<self::Token> {
+        assert!(
+            self.input.is_char_boundary(byte_offset),
+            "The offset should be on a character boundary"
+        );
+
+        self.tokens.clear();
+        self.read_tokens = 0;
+        self.restart_from(byte_offset);
+
+        self.run();
+
+        &self.tokens
+    }
+
+    /**
+     * Swaps in the edited input and lexes it again starting from
+     * byte_offset, the text before it must be left as it was
+     * The tokens before byte_offset are kept, so an edit is
+     * lexed again without lexing the whole input
+     */
+    pub fn lex_edited_from(&mut self, input: &'a str, byte_offset: usize) -> &Vec<self::Token> {
+        assert!(
+            input.is_char_boundary(byte_offset),
+            "The offset should be on a character boundary"
+        );
+
+        self.input = input;
+        self.tokens.drain(..self.read_tokens);
+        self.read_tokens = 0;
+        let kept_token_count = self
+            .tokens
+            .partition_point(|token| token.span.start < byte_offset);
+        self.tokens.truncate(kept_token_count);
+        self.restart_from(byte_offset);
+
+        self.run();
+
+        &self.tokens
+    }
+
+    // puts the lexer back in the start state, with
+    // the cursor on the character at byte_offset
+    fn restart_from(&mut self, byte_offset: usize) {
+        self.handler.clear_from(byte_offset);
+        self.reset_state();
+        self.finished = false;
+        self.previous_string_start = None;
+        self.statement_string_kind = None;
+        self.last_token_end = byte_offset;
+
+        // the line starts before the offset may not have been seen yet
+        self.line_starts.truncate(1);
+        if self.options.track_positions {
+            for (byte_index, character) in self.input[..byte_offset].char_indices() {
+                if self.is_line_break(byte_index, character) {
+                    self.line_starts.push(byte_index + character.len_utf8());
+                }
+            }
+        }
+        self.rewind_cursor(byte_offset);
+    }
+
+    /**
+     * Lexes the input and hands back the tokens, consuming the lexer
+     * so the tokens can outlive it
+     */
+    pub fn into_tokens(mut self) -> Vec<self::Token> {
+        self.run();
+
+        self.tokens
+    }
+
+    /**
+     * Lexes the input and returns the tokens only when there were no errors,
+     * otherwise the errors are moved out of the handler and returned
+     */
+    pub fn lex_checked(mut self) -> Result<Vec<self::Token>, Vec<LexerError>> {
+        self.run();
+
+        if self.handler.has_errors() {
+            Err(std::mem::take(&mut self.handler.errors))
+        } else {
+            Ok(self.tokens)
+        }
+    }
+
+    /**
+     * Lexes like lex, but calls on_diagnostic with each error
+     * and warning in the order they're produced, right after the
+     * character producing them is lexed, they still end up
+     * in the handler afterwards
+     */
+    pub fn lex_with_diagnostics<F: FnMut(&LexerError)>(
+        &mut self,
+        mut on_diagnostic: F,
+    ) -> &Vec<self::Token> {
+        self.run_with_diagnostics(&mut on_diagnostic);
+
+        &self.tokens
+    }
+
+    /**
+     * Runs the state machine over the whole input,
+     * the tokens are accumulated in self.tokens
+     */
+    fn run(&mut self) {
+        self.run_with_diagnostics(&mut |_| {});
+    }
+
+    fn run_with_diagnostics(&mut self, on_diagnostic: &mut dyn FnMut(&LexerError)) {
+        // the tokens handed out by next_token aren't lexed again
+        self.tokens.drain(..self.read_tokens);
+        self.read_tokens = 0;
+        // number of errors and warnings already passed to on_diagnostic
+        let mut notified = (self.handler.errors.len(), self.handler.warnings.len());
+        self.reported_severities = Some(Vec::new());
+
+        while !self.finished {
+            self.step();
+            self.notify_diagnostics(&mut notified, on_diagnostic);
+        }
+        self.reported_severities = None;
+    }
+
+    /**
+     * Lexes until the next token is produced and returns it,
+     * or returns None once the whole input has been lexed
+     */
+    pub fn next_token(&mut self) -> Option<self::Token> {
+        while self.read_tokens == self.tokens.len() && !self.finished {
+            self.step();
+        }
+
+        let token = self.tokens.get(self.read_tokens).cloned()?;
+        self.read_tokens += 1;
+        if self.read_tokens == self.tokens.len() {
+            self.tokens.clear();
+            self.read_tokens = 0;
+        }
+
+        Some(token)
+    }
+
+    /**
+     * Lexes until the next token is produced and returns it without
+     * consuming it, the following next_token call returns it
+     * The token is read in place, the buffered tokens aren't moved
+     */
+    pub fn peek_token(&mut self) -> Option<&self::Token> {
+        while self.read_tokens == self.tokens.len() && !self.finished {
+            self.step();
+        }
+
+        self.tokens.get(self.read_tokens)
+    }
+
+    /**
+     * Processes the character the cursor is on in the current state,
+     * the handlers move the cursor past the characters they use
+     * At the end of the input, the last buffered token is consumed instead
+     */
+    fn step(&mut self) {
+        if self.handler.reached_max_errors() {
+            self.abort();
+            return;
+        }
+
+        let Some((current_character_byte_index, current_character)) = self.current_group else {
+            self.finish();
+            return;
+        };
+        self.current_character_byte_index = current_character_byte_index;
+
+        match self.current_state {
+            State::Start => self.handle_start(current_character),
+            State::InIdentifier => self.handle_in_identifier(current_character),
+            State::InString(_) => self.handle_in_string(current_character),
+            State::InNumber => self.handle_in_number(current_character),
+            State::InOperator => self.handle_in_operator(current_character),
+            State::InWhitespace => self.handle_in_whitespace(current_character),
+            State::InComment => self.handle_in_comment(current_character),
+            State::InBlockComment { .. } => self.handle_in_block_comment(current_character),
+            State::InError => self.handle_in_error(current_character),
+        }
+    }
+
+    /**
+     * Stops the lexing where the cursor is, without
+     * consuming the token being buffered
+     */
+    fn abort(&mut self) {
+        self.finished = true;
+
+        let cursor_byte_index = self
+            .current_group
+            .map_or(self.input.len(), |(byte_index, _)| byte_index);
+        self.report(LexerError {
+            span: Span::new(cursor_byte_index, 0),
+            kind: LexerErrorKind::TooManyErrors,
+        });
+    }
+
+    fn finish(&mut self) {
+        self.finished = true;
+
+        // consume the last buffered token
+        // if the state machine is still in a non-start state
+        if self.current_state != State::Start {
+            // advance the character index so that the last
+            // character is included in the buffered token
+            self.current_character_byte_index = self.input.len();
+
+            let unterminated_kind = match self.current_state {
+                State::InBlockComment { .. } => Some(LexerErrorKind::UnterminatedComment),
+                // the closing quote was never found
+                State::InString(_) => Some(LexerErrorKind::UnterminatedString),
+                _ => None,
+            };
+            let span = self.create_current_token_span();
+
+            let token_count = self.tokens.len();
+            self.consume_buffered_token();
+
+            // the last token may have been skipped
+            if self.options.mark_incomplete_tokens && self.tokens.len() > token_count {
+                if let Some(token) = self.tokens.last_mut() {
+                    token.incomplete = true;
+                }
+            }
+
+            // reported once the token is consumed, after
+            // the diagnostics about the token itself
+            if let Some(kind) = unterminated_kind {
+                self.report(LexerError { span, kind });
+            }
+        }
+
+        if self.options.emit_eof {
+            let token = token::create_token(TokenKind::Eof, self.input.len(), 0);
+            self.consume_token_explicit(token);
+        }
+    }
+
+    /**
+     * Passes the errors and warnings produced since the last
+     * call to on_diagnostic, in the order they were produced
+     * Errors and warnings are kept apart by the handler, so
+     * the reported severities tell which one comes next
+     */
+    fn notify_diagnostics(
+        &mut self,
+        notified: &mut (usize, usize),
+        on_diagnostic: &mut dyn FnMut(&LexerError),
+    ) {
+        let mut reported_severities = self.reported_severities.take().unwrap_or_default();
+
+        for severity in reported_severities.drain(..) {
+            let diagnostic = match severity {
+                Severity::Error => {
+                    notified.0 += 1;
+                    &self.handler.errors[notified.0 - 1]
+                }
+                Severity::Warning => {
+                    notified.1 += 1;
+                    &self.handler.warnings[notified.1 - 1]
+                }
+            };
+            on_diagnostic(diagnostic);
+        }
+
+        // the buffer is reused for the next step
+        self.reported_severities = Some(reported_severities);
+    }
+
+    /**
+     * Creates a span from buffered_token_start, which is the
+     * byte index of the first character of the token being buffered
      */
     fn create_current_token_span(&self) -> Span {
         // if the buffered token is empty
         // we're only processing a single character
         let token_length = {
-            let l = self.get_buffered_token().len();
+            let l = self.buffered_token_length();
             if l == 0 {
                 1
             } else {
@@ -254,129 +1442,3568 @@ impl<'a> Lexer<'a> {
             }
         };
 
-        Span::new(self.buffered_token_start, token_length)
+        Span::new(self.buffered_token_start, token_length)
+    }
+
+    /**
+     * Gets a slice from the input string that represents the buffered token
+     * On a defined character index, the buffered token is the slice
+     * from the buffered_token_start to the preceding character indice
+     * (slices are exclusive on the end index)
+     */
+    fn get_buffered_token(&self) -> &str {
+        &self.input[self.buffered_token_start..self.current_character_byte_index]
+    }
+
+    /**
+     * Gets the length in bytes of the buffered token, without slicing
+     * the input, since the handlers check it on every character
+     */
+    fn buffered_token_length(&self) -> usize {
+        self.current_character_byte_index - self.buffered_token_start
+    }
+
+    /**
+     * Gets the character following the one being processed,
+     * without moving the cursor
+     * It's the lookahead handlers use to make their decisions
+     */
+    fn peek_next_char(&self) -> Option<char> {
+        let mut characters = self.input[self.current_character_byte_index..].chars();
+        characters.next();
+        characters.next()
+    }
+
+    /**
+     * Checks if the character can start an identifier,
+     * a `$` can when it's allowed in identifiers
+     */
+    fn is_identifier_start(&self, character: char) -> bool {
+        character_helpers::is_identifier_start(character)
+            || (self.options.allow_dollar_in_identifiers
+                && character_helpers::is_dollar_sign(character))
+    }
+
+    fn is_in_identifier(&self, character: char) -> bool {
+        character_helpers::is_in_identifier(character)
+            || (self.options.allow_dollar_in_identifiers
+                && character_helpers::is_dollar_sign(character))
+            || self.is_normalized_combining_mark(character)
+    }
+
+    // identifiers that are normalized can be written in their
+    // decomposed form, where accents are separate combining marks
+    #[cfg(feature = "unicode-normalization")]
+    fn is_normalized_combining_mark(&self, character: char) -> bool {
+        self.options.normalize_identifiers && character_helpers::is_combining_mark(character)
+    }
+
+    #[cfg(not(feature = "unicode-normalization"))]
+    fn is_normalized_combining_mark(&self, _character: char) -> bool {
+        false
+    }
+
+    /**
+     * Gets the number of hashes of the raw string the character
+     * starts, or None if it doesn't start one
+     */
+    fn raw_string_hash_count(&self, character: char) -> Option<usize> {
+        if !self.options.raw_strings || character != 'r' {
+            return None;
+        }
+
+        let after_prefix = &self.input[self.current_character_byte_index + 1..];
+        let hash_count = after_prefix.len() - after_prefix.trim_start_matches('#').len();
+        after_prefix[hash_count..]
+            .starts_with('"')
+            .then_some(hash_count)
+    }
+
+    /**
+     * Gets the delimiter closing the custom string the
+     * character opens, or None if it doesn't open one
+     */
+    fn custom_string_close(&self, character: char) -> Option<char> {
+        self.options
+            .string_delimiters
+            .iter()
+            .find(|(open, _)| *open == character)
+            .map(|(_, close)| *close)
+    }
+
+    /**
+     * Checks if the character starts a line break
+     * that's lexed as a newline token
+     */
+    fn is_newline_token_start(&self, character: char) -> bool {
+        self.options.newline_tokens
+            && (character_helpers::is_newline(character)
+                || character_helpers::is_carriage_return(character))
+    }
+
+    /**
+     * Checks if the character can start a token with the
+     * current options, the characters that can't are invalid
+     */
+    fn is_token_start(&self, character: char) -> bool {
+        character_helpers::is_token_start(character)
+            || self.is_identifier_start(character)
+            || self.custom_string_close(character).is_some()
+            || self.is_attribute_start(character)
+    }
+
+    /**
+     * Checks if the character being processed is the first slash
+     * of a line comment (`//`)
+     */
+    fn is_line_comment_start(&self, character: char) -> bool {
+        character_helpers::is_slash(character)
+            && self
+                .peek_next_char()
+                .is_some_and(character_helpers::is_slash)
+    }
+
+    /**
+     * Checks if the character being processed is the slash
+     * of a block comment's opening delimiter (a slash followed by an asterisk)
+     */
+    fn is_block_comment_start(&self, character: char) -> bool {
+        character_helpers::is_slash(character)
+            && self
+                .peek_next_char()
+                .is_some_and(character_helpers::is_asterisk)
+    }
+
+    /**
+     * Moves the cursor back to the character at the given byte index,
+     * the characters following it will be processed again
+     */
+    fn rewind_cursor(&mut self, byte_index: usize) {
+        // the characters before byte_index were already processed,
+        // there's no need to walk over them again
+        self.characters = self.input[byte_index..].char_indices().peekable();
+        self.characters_offset = byte_index;
+        self.current_group = self.next_character();
+
+        // the lines after the cursor will be seen again
+        let seen_line_count = self
+            .line_starts
+            .partition_point(|&line_start| line_start <= byte_index);
+        self.line_starts.truncate(seen_line_count);
+    }
+
+    /**
+     * Checks if the buffered number can be represented,
+     * the token keeps its span either way
+     */
+    fn buffered_number_fits(&self) -> bool {
+        let number = self.get_buffered_token();
+        if number.contains('.') {
+            number.parse::<f64>().is_ok_and(f64::is_finite)
+        } else {
+            number.parse::<i64>().is_ok()
+        }
+    }
+
+    /**
+     * Counts the characters between the quotes of the buffered
+     * char literal, an escaped character counts as one
+     */
+    fn char_literal_length(&self) -> usize {
+        let buffered_token = self.get_buffered_token();
+        // the literal may have no closing quote at the end of the input
+        let content = buffered_token[1..]
+            .strip_suffix('\'')
+            .unwrap_or(&buffered_token[1..]);
+
+        let mut length = 0;
+        let mut characters = content.chars();
+        while let Some(character) = characters.next() {
+            if character == self.options.escape_character {
+                characters.next();
+            }
+            length += 1;
+        }
+
+        length
+    }
+
+    /**
+     * Moves the cursor to the next character, each character
+     * of the input is visited once
+     * The character being processed stays the same
+     * until the next step
+     */
+    fn advance_cursor(&mut self) {
+        // every character is passed through here exactly once,
+        // even the ones skipped by the handlers
+        if let Some((byte_index, character)) = self.current_group {
+            if self.options.track_positions && self.is_line_break(byte_index, character) {
+                self.line_starts.push(byte_index + character.len_utf8());
+            }
+        }
+        self.current_group = self.next_character();
+    }
+
+    // the character after the cursor, with its index in the whole input
+    fn next_character(&mut self) -> Option<(usize, char)> {
+        self.characters
+            .next()
+            .map(|(byte_index, character)| (self.characters_offset + byte_index, character))
+    }
+
+    /**
+     * Checks if the character ends a line, `\r\n` is a single
+     * line break ended by its `\n`, and a lone `\r` is one too
+     */
+    fn is_line_break(&self, byte_index: usize, character: char) -> bool {
+        character_helpers::is_newline(character)
+            || (character_helpers::is_carriage_return(character)
+                && !self.input[byte_index + 1..].starts_with('\n'))
+    }
+
+    fn consume_buffered_token(&mut self) {
+        let token_kind = match &self.current_state {
+            State::InIdentifier => {
+                // if the identifier is a literal or matches a keyword,
+                // consume the token as that literal or keyword
+                let buffered_token = self.get_buffered_token();
+                if self.options.ascii_identifiers_only && !buffered_token.is_ascii() {
+                    self.report(LexerError {
+                        span: self.create_current_token_span(),
+                        kind: LexerErrorKind::NonAsciiIdentifier,
+                    });
+
+                    if self.options.drop_invalid_tokens {
+                        return;
+                    }
+                    TokenKind::Invalid
+                } else if character_helpers::is_boolean(buffered_token) {
+                    TokenKind::Boolean
+                } else if character_helpers::is_null(buffered_token) {
+                    TokenKind::Null
+                } else {
+                    match self.options.keywords.get(buffered_token) {
+                        Some(keyword_kind) => TokenKind::Keyword(*keyword_kind),
+                        None => TokenKind::Identifier,
+                    }
+                }
+            }
+            State::InString(string_state) => {
+                match string_state {
+                    StringState::InSingleQuote => {
+                        if !self.options.char_literals {
+                            TokenKind::String(StringKind::SingleQuoted)
+                        } else if self.char_literal_length() == 1 {
+                            TokenKind::Char
+                        } else {
+                            self.report(LexerError {
+                                span: self.create_current_token_span(),
+                                kind: LexerErrorKind::InvalidCharLiteral,
+                            });
+                            TokenKind::Invalid
+                        }
+                    }
+                    StringState::InDoubleQuote => {
+                        if self.options.double_quoted_identifiers {
+                            TokenKind::Identifier
+                        } else {
+                            TokenKind::String(StringKind::DoubleQuoted)
+                        }
+                    }
+                    StringState::InBacktick => TokenKind::String(StringKind::Backtick),
+                    StringState::InCustom { .. } => TokenKind::String(StringKind::Custom),
+                }
+            },
+            State::InNumber => {
+                if self.options.check_number_range && !self.buffered_number_fits() {
+                    self.report(LexerError {
+                        span: self.create_current_token_span(),
+                        kind: LexerErrorKind::NumberOutOfRange,
+                    });
+                }
+                TokenKind::Number
+            }
+            State::InWhitespace => TokenKind::Whitespace,
+            State::InComment => TokenKind::Comment,
+            State::InBlockComment { max_depth, .. } => TokenKind::BlockComment {
+                max_depth: *max_depth,
+            },
+            State::InError => {
+                let found = self
+                    .get_buffered_token()
+                    .chars()
+                    .next()
+                    .expect("The buffered token should never be empty");
+                self.report(LexerError {
+                    span: self.create_current_token_span(),
+                    kind: LexerErrorKind::InvalidToken { found },
+                });
+
+                if self.options.drop_invalid_tokens {
+                    return;
+                }
+                TokenKind::Invalid
+            }
+            State::InOperator => {
+                let buffered_token = self.get_buffered_token();
+                let operator_kind = token::match_operator_slice_to_operator_kind(buffered_token);
+                // if it's doesn't match any valid operator, it's a compound-like operator
+                // We should split the operator from its longest valid prefix,
+                // consume that prefix and then reprocess the rest of the operator
+                match operator_kind {
+                    OperatorKind::Invalid => {
+                        self.report(LexerError {
+                            span: self.create_current_token_span(),
+                            kind: LexerErrorKind::InvalidOperator,
+                        });
+
+                        loop {
+                            let buffered_token = self.get_buffered_token();
+                            let (prefix_kind, rest) = token::split_compound_operator(buffered_token);
+
+                            if rest.is_empty() {
+                                break TokenKind::Operator(prefix_kind);
+                            }
+
+                            let prefix_length = buffered_token.len() - rest.len();
+
+                            let prefix_token = token::create_token(TokenKind::Operator(prefix_kind), self.buffered_token_start, prefix_length);
+                            self.consume_token_explicit(prefix_token);
+
+                            self.buffered_token_start += prefix_length;
+                        }
+                    },
+                    _ => TokenKind::Operator(operator_kind),
+                }
+            },
+            // NOTE: this arm will never be matched
+            // it's a bug if it does
+            State::Start => unreachable!("This function should never be called to buffer tokens when the lexer is in a `Start` state. Use `consume_token_explicit`"),
+        };
+
+        let token = Token {
+            kind: token_kind,
+            span: self.create_current_token_span(),
+            incomplete: false,
+        };
+
+        // the cursor is one character ahead of the last character
+        // of the token
+        // so the the start of the next token is the current cursor position
+        self.consume_token_explicit(token);
+    }
+
+    /**
+     * Helper method to consume a token created on the fly
+     * The cursor would likely need to to incremented
+     * as the character/string would've been used to
+     * create the token
+     */
+    fn consume_token_explicit(&mut self, mut token: Token) {
+        // tokens are consumed in the order of the input, a token
+        // overlapping the previous one is a bug in a handler
+        debug_assert!(
+            token.span.start >= self.last_token_end,
+            "The token at {} starts before the end of the previous one at {}",
+            token.span.start,
+            self.last_token_end
+        );
+        self.last_token_end = token.span.end();
+
+        let is_comment = matches!(
+            token.kind,
+            TokenKind::Comment | TokenKind::BlockComment { .. }
+        );
+        if is_comment && self.options.skip_comments {
+            return;
+        }
+
+        token.span = self.locate_span(token.span);
+        if self.options.forbid_adjacent_strings {
+            self.check_adjacent_strings(&token);
+        }
+        if self.options.warn_mixed_quotes {
+            self.check_mixed_quotes(&token);
+        }
+        self.tokens.push(token);
+    }
+
+    fn report(&mut self, mut diagnostic: LexerError) {
+        diagnostic.span = self.locate_span(diagnostic.span);
+        if let Some(reported_severities) = &mut self.reported_severities {
+            reported_severities.push(diagnostic.severity());
+        }
+        self.handler.add(diagnostic);
+    }
+
+    /**
+     * Sets the line and column of the span when positions are tracked
+     * The line of the span's start must have been seen already
+     */
+    fn locate_span(&self, mut span: Span) -> Span {
+        if self.options.track_positions {
+            let line = self
+                .line_starts
+                .partition_point(|&line_start| line_start <= span.start);
+            let line_start = self.line_starts[line - 1];
+
+            span.line = Some(line);
+            span.column = Some(self.input[line_start..span.start].chars().count() + 1);
+        }
+
+        span
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Token;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_token()
+    }
+}
+
+// checks over the produced tokens
+impl Lexer<'_> {
+    fn check_adjacent_strings(&mut self, token: &Token) {
+        match token.kind {
+            TokenKind::String(_) => {
+                if let Some(previous_start) = self.previous_string_start {
+                    // the warning covers both string literals
+                    let length = token.span.start + token.span.length - previous_start;
+
+                    self.report(LexerError {
+                        span: Span::new(previous_start, length),
+                        kind: LexerErrorKind::AdjacentStringLiterals,
+                    });
+                }
+                self.previous_string_start = Some(token.span.start);
+            }
+            // whitespace and comments don't separate string literals
+            _ if token.kind.is_trivia() => {}
+            _ => self.previous_string_start = None,
+        }
+    }
+
+    /**
+     * Warns about the string if it isn't quoted like the first
+     * string of the statement, the same check as the
+     * mixed_quotes_in_expression helper, done while lexing
+     */
+    fn check_mixed_quotes(&mut self, token: &Token) {
+        match token.kind {
+            TokenKind::String(string_kind) => match self.statement_string_kind {
+                None => self.statement_string_kind = Some(string_kind),
+                Some(first_string_kind) if first_string_kind != string_kind => {
+                    self.report(LexerError {
+                        span: token.span,
+                        kind: LexerErrorKind::MixedQuotes,
+                    });
+                }
+                Some(_) => {}
+            },
+            TokenKind::Semicolon => self.statement_string_kind = None,
+            _ => {}
+        }
+    }
+}
+
+// convenience functions
+/**
+ * Lexes the source with the default options,
+ * and returns its tokens along with the errors
+ */
+pub fn tokenize(source: &str) -> (Vec<Token>, Vec<LexerError>) {
+    let mut handler = ErrorHandler::new();
+    let lexer = Lexer::new(source, &mut handler);
+
+    let tokens = lexer.into_tokens();
+
+    (tokens, handler.errors)
+}
+
+/**
+ * Reads the file at the path and tokenizes it, the file
+ * has to be valid UTF-8 or an InvalidData error is returned
+ */
+pub fn lex_file(path: impl AsRef<Path>) -> io::Result<(Vec<Token>, Vec<LexerError>)> {
+    let source = std::fs::read_to_string(path)?;
+
+    Ok(tokenize(&source))
+}
+
+/**
+ * Lexes the source and checks that the kinds of its significant
+ * tokens (everything but whitespace and comments) are exactly
+ * the ones in the pattern
+ */
+pub fn matches_pattern(source: &str, pattern: &[TokenKind]) -> bool {
+    let mut handler = ErrorHandler::new();
+    let mut lexer = Lexer::new(source, &mut handler);
+
+    let tokens = lexer.lex();
+
+    tokens
+        .iter()
+        .filter(|token| token_helpers::is_significant(token))
+        .map(|token| &token.kind)
+        .eq(pattern)
+}
+
+/**
+ * Gets the name of an identifier token, which is the text it spans
+ * When identifiers are normalized, the name is in its NFC form
+ */
+#[cfg(feature = "unicode-normalization")]
+pub fn identifier_name<'s>(
+    token: &Token,
+    source: &'s str,
+    options: &LexerOptions,
+) -> std::borrow::Cow<'s, str> {
+    use unicode_normalization::UnicodeNormalization;
+
+    let text = token.text(source);
+
+    if options.normalize_identifiers {
+        std::borrow::Cow::Owned(text.nfc().collect())
+    } else {
+        std::borrow::Cow::Borrowed(text)
+    }
+}
+
+/**
+ * Gets the value of a string token, which is its text without
+ * the quotes and with its escape sequences decoded
+ */
+pub fn string_value(token: &Token, source: &str, options: &LexerOptions) -> String {
+    let text = token.text(source);
+    if token.kind == TokenKind::String(StringKind::Raw) {
+        // there's nothing to decode in a raw string, the
+        // value is between the `r#"` and the `"#`
+        let after_prefix = &text[1..];
+        let hash_count = after_prefix.len() - after_prefix.trim_start_matches('#').len();
+        let content = &after_prefix[hash_count + 1..];
+        let closing_delimiter = format!("\"{}", "#".repeat(hash_count));
+        // the raw string may have no closing delimiter at the end of the input
+        return String::from(content.strip_suffix(&closing_delimiter).unwrap_or(content));
+    }
+
+    let mut characters = text.chars();
+    let opening_quote = characters.next();
+    // only custom strings are closed by another delimiter
+    let quote = if token.kind == TokenKind::String(StringKind::Custom) {
+        options
+            .string_delimiters
+            .iter()
+            .find(|(open, _)| Some(*open) == opening_quote)
+            .map_or(opening_quote, |(_, close)| Some(*close))
+    } else {
+        opening_quote
+    };
+
+    let mut value = String::new();
+    while let Some(character) = characters.next() {
+        if character == options.escape_character {
+            match characters.next() {
+                Some('n') => value.push('\n'),
+                Some('t') => value.push('\t'),
+                Some('r') => value.push('\r'),
+                Some('0') => value.push('\0'),
+                // any other escaped character stands for itself
+                Some(escaped_character) => value.push(escaped_character),
+                None => {}
+            }
+        } else if Some(character) == quote {
+            break;
+        } else {
+            value.push(character);
+        }
+    }
+
+    value
+}
+
+/**
+ * Lexes a source that should only contain an expression
+ * The first lexing error, or the first token that can only
+ * be found in a statement (a semicolon or a declaration,
+ * control flow or loop keyword) is returned as an error
+ */
+pub fn lex_expression(source: &str) -> Result<Vec<Token>, LexerError> {
+    let mut handler = ErrorHandler::new();
+    let lexer = Lexer::new(source, &mut handler);
+
+    let tokens = lexer.into_tokens();
+
+    if let Some(error) = handler.errors.into_iter().next() {
+        return Err(error);
+    }
+
+    let statement_token = tokens.iter().find(|token| match &token.kind {
+        TokenKind::Semicolon => true,
+        TokenKind::Keyword(keyword_kind) => matches!(
+            keyword_kind.category(),
+            KeywordCategory::Declaration | KeywordCategory::ControlFlow | KeywordCategory::Loop
+        ),
+        _ => false,
+    });
+
+    match statement_token {
+        Some(token) => Err(LexerError {
+            span: token.span,
+            kind: LexerErrorKind::StatementInExpression,
+        }),
+        None => Ok(tokens),
+    }
+}
+
+/**
+ * Lexes the source, returning its tokens when there are no errors
+ * Otherwise, returns a report of all the errors, ready to be printed
+ */
+pub fn lex_and_report(source: &str) -> Result<Vec<Token>, String> {
+    let mut handler = ErrorHandler::new();
+    let lexer = Lexer::new(source, &mut handler);
+
+    let tokens = lexer.into_tokens();
+
+    if handler.errors.is_empty() {
+        return Ok(tokens);
+    }
+
+    let report = handler
+        .errors
+        .iter()
+        .map(|error| error.render(source))
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    Err(report)
+}
+
+// TODO: consider snapshot testing instead of fixtures
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn it_tokenizes_basic_number_assignment_correctly() {
+        let source = "let value = 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        // assert_eq!(tokens.len(), 8);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::Number, 12, 1),
+                token::create_token(TokenKind::Semicolon, 13, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_number_compound_assignment_correctly() {
+        let source = "let value += 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::CompoundAdd), 10, 2),
+                token::create_token(TokenKind::Whitespace, 12, 1),
+                token::create_token(TokenKind::Number, 13, 1),
+                token::create_token(TokenKind::Semicolon, 14, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_invalid_operator_correctly_1() {
+        let source = "let value =+ 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 11, 1),
+                token::create_token(TokenKind::Whitespace, 12, 1),
+                token::create_token(TokenKind::Number, 13, 1),
+                token::create_token(TokenKind::Semicolon, 14, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_invalid_operator_correctly_2() {
+        let source = "let value %=+ 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::CompoundModulo), 10, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 12, 1),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Number, 14, 1),
+                token::create_token(TokenKind::Semicolon, 15, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_invalid_operator_correctly_3() {
+        let source = "let value ++++ 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Increment), 10, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::Increment), 12, 2),
+                token::create_token(TokenKind::Whitespace, 14, 1),
+                token::create_token(TokenKind::Number, 15, 1),
+                token::create_token(TokenKind::Semicolon, 16, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_modulo_chains_correctly() {
+        // %% isn't an operator, but %=%= is two of them side by side
+        let sources_and_operators = [
+            (
+                "a %% b",
+                vec![(OperatorKind::Modulo, 2, 1), (OperatorKind::Modulo, 3, 1)],
+                1,
+            ),
+            (
+                "a %=%= b",
+                vec![
+                    (OperatorKind::CompoundModulo, 2, 2),
+                    (OperatorKind::CompoundModulo, 4, 2),
+                ],
+                0,
+            ),
+        ];
+
+        for (source, operators, error_count) in sources_and_operators {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            let expected_operators: Vec<_> = operators
+                .into_iter()
+                .map(|(kind, start, length)| {
+                    token::create_token(TokenKind::Operator(kind), start, length)
+                })
+                .collect();
+            assert_eq!(&tokens[2..tokens.len() - 2], &expected_operators);
+            assert_eq!(handler.errors.len(), error_count);
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_number_post_increment_correctly() {
+        let source = "let value = 1;\nvalue++;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 12);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::Number, 12, 1),
+                token::create_token(TokenKind::Semicolon, 13, 1),
+                token::create_token(TokenKind::Whitespace, 14, 1),
+                token::create_token(TokenKind::Identifier, 15, 5),
+                token::create_token(TokenKind::Operator(OperatorKind::Increment), 20, 2),
+                token::create_token(TokenKind::Semicolon, 22, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_peeks_the_next_character_correctly() {
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new("a/пр", &mut handler);
+
+        let peeks = (0..lexer.input.len())
+            .filter(|byte_index| lexer.input.is_char_boundary(*byte_index))
+            .map(|byte_index| {
+                lexer.current_character_byte_index = byte_index;
+                lexer.peek_next_char()
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(peeks, vec![Some('/'), Some('п'), Some('р'), None]);
+
+        let mut handler = ErrorHandler::new();
+        let lexer = Lexer::new("", &mut handler);
+        assert_eq!(lexer.peek_next_char(), None);
+    }
+
+    #[test]
+    fn it_tokenizes_a_byte_slice_correctly() {
+        let bytes = "let greetings = 'привет';".as_bytes();
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::from_bytes(bytes, &mut handler).unwrap();
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 9),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 14, 1),
+                token::create_token(TokenKind::Whitespace, 15, 1),
+                token::create_token(TokenKind::String(StringKind::SingleQuoted), 16, 14),
+                token::create_token(TokenKind::Semicolon, 30, 1),
+            ]
+        );
+
+        let mut handler = ErrorHandler::new();
+        assert!(Lexer::from_bytes(&[b'x', 0xff], &mut handler).is_err());
+    }
+
+    #[test]
+    fn it_lexes_a_new_source_after_a_reset() {
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new("let @ = 'unterminated", &mut handler);
+        assert_eq!(lexer.lex().len(), 7);
+        // the invalid `@` and the unterminated string
+        assert_eq!(lexer.handler().errors().len(), 2);
+
+        lexer.reset("x + 2");
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Number, 4, 1),
+            ]
+        );
+        assert!(lexer.handler().errors().is_empty());
+        assert!(lexer.handler().warnings().is_empty());
+    }
+
+    #[test]
+    fn it_collects_errors_in_the_handler_of_an_owned_lexer() {
+        let source = "let @ = 1;";
+        let mut lexer = Lexer::owned(source);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(
+            lexer.handler().errors(),
+            [LexerError {
+                span: Span::new(4, 1),
+                kind: LexerErrorKind::InvalidToken { found: '@' },
+            }]
+        );
+        assert!(ErrorHandler::default().errors().is_empty());
+    }
+
+    #[test]
+    fn it_tokenizes_cyrillic_strings_correctly() {
+        let source = "let greetings = 'привет мой друг';";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 9),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 14, 1),
+                token::create_token(TokenKind::Whitespace, 15, 1),
+                token::create_token(TokenKind::String(StringKind::SingleQuoted), 16, 30),
+                token::create_token(TokenKind::Semicolon, 46, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_counts_the_characters_of_multibyte_spans_correctly() {
+        let source = "let greetings = 'привет мой друг';";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        let string_span = &tokens[6].span;
+        assert_eq!(string_span.length, 30);
+        assert_eq!(string_span.char_length(source), 17);
+        // ascii characters are a single byte
+        assert_eq!(tokens[0].span.char_length(source), tokens[0].span.length);
+    }
+
+    #[test]
+    fn it_rebuilds_the_source_from_the_tokens() {
+        let sources = [
+            "let word = \"Hello\" + \" \" + \"world!\"; ",
+            "let greetings = 'привет мой друг';",
+            "let @$# = &&| something something;",
+            "/* a /* nested */ comment */ x >>>= 'unterminated",
+            include_str!("../tests/fixtures/valid.txt"),
+            include_str!("../tests/fixtures/invalid.txt"),
+        ];
+
+        for source in sources {
+            let mut handler = ErrorHandler::new();
+            let options = LexerOptions {
+                emit_eof: true,
+                ..LexerOptions::default()
+            };
+            let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(to_source(tokens, source), source);
+        }
+    }
+
+    #[test]
+    fn it_extracts_the_text_of_tokens_correctly() {
+        let source = "let word = \"Hello\" + 'привет';";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens[6].text(source), "\"Hello\"");
+        assert_eq!(tokens[10].text(source), "'привет'");
+    }
+
+    #[test]
+    fn it_splits_operators_at_the_end_of_the_input_like_mid_stream() {
+        let options = || LexerOptions {
+            max_operator_length: 3,
+            ..LexerOptions::default()
+        };
+
+        for operators in ["++++", "=+", "%=+", "!=="] {
+            let source = format!("a {operators}");
+            let mut handler = ErrorHandler::new();
+            let lexer = Lexer::with_options(&source, &mut handler, options());
+            let tokens = lexer.into_tokens();
+
+            let terminated_source = format!("a {operators};");
+            let mut terminated_handler = ErrorHandler::new();
+            let lexer = Lexer::with_options(&terminated_source, &mut terminated_handler, options());
+            let mut terminated_tokens = lexer.into_tokens();
+
+            assert_eq!(
+                terminated_tokens.pop(),
+                Some(token::create_token(TokenKind::Semicolon, source.len(), 1))
+            );
+            assert_eq!(tokens, terminated_tokens);
+            assert_eq!(handler.errors, terminated_handler.errors);
+        }
+
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new("a ++++", &mut handler);
+
+        assert_eq!(
+            lexer.lex(),
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Increment), 2, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::Increment), 4, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_computes_token_stats_correctly() {
+        let source = "let word = \"Hello\" + \" \" + \"world!\"; ";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            token_stats(tokens),
+            TokenStats {
+                keywords: 1,
+                identifiers: 1,
+                numbers: 0,
+                strings: 3,
+                operators: 3,
+                errors: 0,
+            }
+        );
+
+        let (tokens, _) = tokenize("x = @ 1 + 2 $");
+        assert_eq!(token_stats(&tokens).numbers, 2);
+        assert_eq!(token_stats(&tokens).errors, 2);
+    }
+
+    #[test]
+    fn it_finds_the_token_at_an_offset_correctly() {
+        let source = "let word = \"Hello\" + \" \" + \"world!\"; ";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        let offsets_and_tokens = [
+            (
+                0,
+                Some(token::create_token(
+                    TokenKind::Keyword(KeywordKind::Let),
+                    0,
+                    3,
+                )),
+            ),
+            (
+                2,
+                Some(token::create_token(
+                    TokenKind::Keyword(KeywordKind::Let),
+                    0,
+                    3,
+                )),
+            ),
+            (3, Some(token::create_token(TokenKind::Whitespace, 3, 1))),
+            (
+                14,
+                Some(token::create_token(
+                    TokenKind::String(StringKind::DoubleQuoted),
+                    11,
+                    7,
+                )),
+            ),
+            (
+                19,
+                Some(token::create_token(
+                    TokenKind::Operator(OperatorKind::Add),
+                    19,
+                    1,
+                )),
+            ),
+            (36, Some(token::create_token(TokenKind::Whitespace, 36, 1))),
+            // the end of the input isn't in any token
+            (37, None),
+            (100, None),
+        ];
+        for (byte_offset, expected_token) in offsets_and_tokens {
+            assert_eq!(
+                token_at(tokens, byte_offset),
+                expected_token.as_ref(),
+                "{byte_offset}"
+            );
+        }
+
+        // skipped whitespace leaves gaps between the tokens
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .skip_whitespace(true)
+            .build(source, &mut handler);
+        assert_eq!(token_at(lexer.lex(), 3), None);
+    }
+
+    #[test]
+    fn it_filters_out_trivia_correctly() {
+        let source = "let x = /* one */ 1; // done\n";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        let significant_tokens: Vec<_> = tokens
+            .iter()
+            .filter(|token| !token.kind.is_trivia())
+            .cloned()
+            .collect();
+        assert_eq!(
+            significant_tokens,
+            vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Number, 18, 1),
+                token::create_token(TokenKind::Semicolon, 19, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_filters_tokens_by_kind_correctly() {
+        let source = "let word = \"Hello\" + \" \" + \"world!\"; ";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens_of_kind(tokens, &TokenKind::Identifier).collect::<Vec<_>>(),
+            vec![&token::create_token(TokenKind::Identifier, 4, 4)]
+        );
+        assert_eq!(
+            tokens_of_kind(tokens, &TokenKind::Operator(OperatorKind::Add)).count(),
+            2
+        );
+        assert_eq!(tokens_of_kind(tokens, &TokenKind::Number).count(), 0);
+    }
+
+    #[test]
+    fn it_tokenizes_empty_strings_correctly() {
+        let quotes = [
+            ('"', StringKind::DoubleQuoted),
+            ('\'', StringKind::SingleQuoted),
+        ];
+        for (quote, string_kind) in quotes {
+            let source = format!("let x = {quote}{quote};");
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(&source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens,
+                &vec![
+                    token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                    token::create_token(TokenKind::Whitespace, 3, 1),
+                    token::create_token(TokenKind::Identifier, 4, 1),
+                    token::create_token(TokenKind::Whitespace, 5, 1),
+                    token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                    token::create_token(TokenKind::Whitespace, 7, 1),
+                    // the span covers both quotes
+                    token::create_token(TokenKind::String(string_kind), 8, 2),
+                    token::create_token(TokenKind::Semicolon, 10, 1),
+                ]
+            );
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_source_with_string_concat_correctly() {
+        let source = "let word = \"Hello\" + \" \" + \"world!\"; ";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 17);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 4),
+                token::create_token(TokenKind::Whitespace, 8, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 9, 1),
+                token::create_token(TokenKind::Whitespace, 10, 1),
+                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 11, 7),
+                token::create_token(TokenKind::Whitespace, 18, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 19, 1),
+                token::create_token(TokenKind::Whitespace, 20, 1),
+                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 21, 3),
+                token::create_token(TokenKind::Whitespace, 24, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 25, 1),
+                token::create_token(TokenKind::Whitespace, 26, 1),
+                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 27, 8),
+                token::create_token(TokenKind::Semicolon, 35, 1),
+                token::create_token(TokenKind::Whitespace, 36, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_correctly_tokenizes_source_with_invalid_tokens() {
+        let source = "let @$# = &&| something something;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 13);
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Invalid, 4, 3),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 8, 1),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::LogicalAnd), 10, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::BitwiseOr), 12, 1),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Identifier, 14, 9),
+                token::create_token(TokenKind::Whitespace, 23, 1),
+                token::create_token(TokenKind::Identifier, 24, 9),
+                token::create_token(TokenKind::Semicolon, 33, 1),
+            ]
+        )
+    }
+
+    #[test]
+    fn it_tokenizes_attributes_when_enabled() {
+        let source = "@override function $@x_1() {}";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .attributes(true)
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Attribute, 0, 9),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Keyword(KeywordKind::Function), 10, 8),
+                token::create_token(TokenKind::Whitespace, 18, 1),
+                token::create_token(TokenKind::Invalid, 19, 1),
+                token::create_token(TokenKind::Attribute, 20, 4),
+                token::create_token(TokenKind::OpenParen, 24, 1),
+                token::create_token(TokenKind::CloseParen, 25, 1),
+                token::create_token(TokenKind::Whitespace, 26, 1),
+                token::create_token(TokenKind::OpenBrace, 27, 1),
+                token::create_token(TokenKind::CloseBrace, 28, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_a_lone_at_sign_as_invalid_when_attributes_are_enabled() {
+        let source = "@;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .attributes(true)
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Invalid, 0, 1),
+                token::create_token(TokenKind::Semicolon, 1, 1),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(0, 1),
+                kind: LexerErrorKind::InvalidToken { found: '@' },
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_non_ascii_identifiers_by_default() {
+        let source = "café";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![token::create_token(TokenKind::Identifier, 0, 5)]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_reports_non_ascii_identifiers_when_rejected() {
+        let source = "café = cafe;";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            ascii_identifiers_only: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Invalid, 0, 5),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Identifier, 8, 4),
+                token::create_token(TokenKind::Semicolon, 12, 1),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(0, 5),
+                kind: LexerErrorKind::NonAsciiIdentifier,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_a_dollar_sign_as_invalid_by_default() {
+        let source = "$x";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Invalid, 0, 1),
+                token::create_token(TokenKind::Identifier, 1, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 1);
+    }
+
+    #[test]
+    fn it_tokenizes_dollar_signs_in_identifiers_when_allowed() {
+        let source = "$x + foo$bar";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            allow_dollar_in_identifiers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 2),
+                token::create_token(TokenKind::Whitespace, 2, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 3, 1),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 7),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_merges_consecutive_invalid_characters() {
+        let source = "@$ x €€";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Invalid, 0, 2),
+                token::create_token(TokenKind::Whitespace, 2, 1),
+                token::create_token(TokenKind::Identifier, 3, 1),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Invalid, 5, 6),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![
+                LexerError {
+                    span: Span::new(0, 2),
+                    kind: LexerErrorKind::InvalidToken { found: '@' },
+                },
+                LexerError {
+                    span: Span::new(5, 6),
+                    kind: LexerErrorKind::InvalidToken { found: '€' },
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_collects_expected_errors() {
+        let source = "let value =+ 1;\nlet @$# = &&| something something;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+        assert_eq!(tokens.len(), 23);
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 11, 1),
+                token::create_token(TokenKind::Whitespace, 12, 1),
+                token::create_token(TokenKind::Number, 13, 1),
+                token::create_token(TokenKind::Semicolon, 14, 1),
+                token::create_token(TokenKind::Whitespace, 15, 1),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 16, 3),
+                token::create_token(TokenKind::Whitespace, 19, 1),
+                token::create_token(TokenKind::Invalid, 20, 3),
+                token::create_token(TokenKind::Whitespace, 23, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 24, 1),
+                token::create_token(TokenKind::Whitespace, 25, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::LogicalAnd), 26, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::BitwiseOr), 28, 1),
+                token::create_token(TokenKind::Whitespace, 29, 1),
+                token::create_token(TokenKind::Identifier, 30, 9),
+                token::create_token(TokenKind::Whitespace, 39, 1),
+                token::create_token(TokenKind::Identifier, 40, 9),
+                token::create_token(TokenKind::Semicolon, 49, 1),
+            ]
+        );
+
+        assert_eq!(handler.errors.len(), 2);
+        assert_eq!(
+            LexerError {
+                span: Span::new(10, 2),
+                kind: LexerErrorKind::InvalidOperator,
+            },
+            handler.errors[0]
+        );
+
+        assert_eq!(
+            LexerError {
+                span: Span::new(20, 3),
+                kind: LexerErrorKind::InvalidToken { found: '@' },
+            },
+            handler.errors[1]
+        );
+    }
+
+    #[test]
+    fn it_correctly_tokenizes_source_when_lexer_state_machine_ends_in_a_non_start_state() {
+        // Here the lexer's state machine will end in a non-start state
+        // more precisely in the InIdentifier state
+        // That's because the identifier is at the end of the source
+        // and its corresponding handler will only consume the buffered
+        // token when it encounters a non-identifier character,
+        // which doesn't happen in this case
+        // It requires special handling (to consume the buffered token when
+        // lexing ends in a non-start state), but this makes the
+        // handlers' code much simpler
+        let source = "let value = another_value";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 7);
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 5),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::Identifier, 12, 13),
+            ]
+        )
+    }
+
+    #[test]
+    fn it_tokenizes_line_comments_correctly() {
+        let source = "let x = 1; // assign\nx // at eof";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 14);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Number, 8, 1),
+                token::create_token(TokenKind::Semicolon, 9, 1),
+                token::create_token(TokenKind::Whitespace, 10, 1),
+                token::create_token(TokenKind::Comment, 11, 9),
+                token::create_token(TokenKind::Whitespace, 20, 1),
+                token::create_token(TokenKind::Identifier, 21, 1),
+                token::create_token(TokenKind::Whitespace, 22, 1),
+                token::create_token(TokenKind::Comment, 23, 9),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_single_slash_as_divide_operator() {
+        let source = "a / b =// c";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 8);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Divide), 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Comment, 7, 4),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_warns_on_adjacent_strings_when_forbidden() {
+        let source = "let x = \"a\" \"b\";";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            forbid_adjacent_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        lexer.lex();
+
+        assert_eq!(handler.errors.len(), 0);
+        assert_eq!(
+            handler.warnings,
+            vec![LexerError {
+                span: Span::new(8, 7),
+                kind: LexerErrorKind::AdjacentStringLiterals,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_stops_lexing_once_the_error_limit_is_reached() {
+        let source = format!("x {}y", "@ ".repeat(20));
+        let mut handler = ErrorHandler::with_max_errors(5);
+        let mut lexer = Lexer::new(&source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Invalid, 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Invalid, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Invalid, 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Invalid, 8, 1),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Invalid, 10, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 6);
+        assert_eq!(
+            handler.errors[5],
+            LexerError {
+                span: Span::new(11, 0),
+                kind: LexerErrorKind::TooManyErrors,
+            }
+        );
+    }
+
+    #[test]
+    fn it_filters_diagnostics_by_severity() {
+        let source = "let x = \"a\" \"b\" =+ 1;";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            forbid_adjacent_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        lexer.lex();
+
+        assert_eq!(
+            handler.errors_with_severity(Severity::Error),
+            &[LexerError {
+                span: Span::new(16, 2),
+                kind: LexerErrorKind::InvalidOperator,
+            }]
+        );
+        assert_eq!(
+            handler.errors_with_severity(Severity::Warning),
+            &[LexerError {
+                span: Span::new(8, 7),
+                kind: LexerErrorKind::AdjacentStringLiterals,
+            }]
+        );
+        assert_eq!(handler.errors()[0].severity(), Severity::Error);
+        assert_eq!(handler.warnings()[0].severity(), Severity::Warning);
+    }
+
+    #[test]
+    fn it_does_not_warn_on_concatenated_strings() {
+        let source = "let x = \"a\" + \"b\";";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            forbid_adjacent_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        lexer.lex();
+
+        assert_eq!(handler.warnings.len(), 0);
+    }
+
+    #[test]
+    fn it_matches_token_kind_patterns() {
+        let pattern = [
+            TokenKind::Keyword(KeywordKind::Let),
+            TokenKind::Identifier,
+            TokenKind::Operator(OperatorKind::Equal),
+            TokenKind::Number,
+            TokenKind::Semicolon,
+        ];
+
+        assert!(matches_pattern("let x = 1;", &pattern));
+        assert!(matches_pattern("let x=1; // trailing comment", &pattern));
+        assert!(!matches_pattern("let x = 1", &pattern));
+        assert!(!matches_pattern("let x = y;", &pattern));
+    }
+
+    #[test]
+    fn it_ends_line_comments_at_crlf_line_breaks() {
+        let source = "// c\r\nx";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            newline_tokens: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Comment, 0, 4),
+                token::create_token(TokenKind::Newline, 4, 2),
+                token::create_token(TokenKind::Identifier, 6, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_ends_line_comments_at_lone_carriage_returns() {
+        let source = "a // c\rx";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Comment, 2, 4),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Identifier, 7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_block_comments_correctly() {
+        let source = "a /* comment */ b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 5);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::BlockComment { max_depth: 1 }, 2, 13),
+                token::create_token(TokenKind::Whitespace, 15, 1),
+                token::create_token(TokenKind::Identifier, 16, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_nested_block_comments_correctly() {
+        let source = "/* outer /* inner */ still comment */x";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 2);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::BlockComment { max_depth: 2 }, 0, 37),
+                token::create_token(TokenKind::Identifier, 37, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 0);
+    }
+
+    #[test]
+    fn it_reports_unterminated_block_comments() {
+        let source = "x /* never /* closed */";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 3);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::BlockComment { max_depth: 2 }, 2, 21),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(2, 21),
+                kind: LexerErrorKind::UnterminatedComment,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_reports_a_lone_quote_at_the_end_of_the_input_as_unterminated() {
+        let sources_and_kinds = [
+            ("\"", StringKind::DoubleQuoted),
+            ("'", StringKind::SingleQuoted),
+        ];
+
+        for (source, string_kind) in sources_and_kinds {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens,
+                &vec![token::create_token(TokenKind::String(string_kind), 0, 1)]
+            );
+            assert_eq!(
+                handler.errors,
+                vec![LexerError {
+                    span: Span::new(0, 1),
+                    kind: LexerErrorKind::UnterminatedString,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_delimiters_correctly() {
+        let source = "function foo() { return [1]; }";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 16);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Function), 0, 8),
+                token::create_token(TokenKind::Whitespace, 8, 1),
+                token::create_token(TokenKind::Identifier, 9, 3),
+                token::create_token(TokenKind::OpenParen, 12, 1),
+                token::create_token(TokenKind::CloseParen, 13, 1),
+                token::create_token(TokenKind::Whitespace, 14, 1),
+                token::create_token(TokenKind::OpenBrace, 15, 1),
+                token::create_token(TokenKind::Whitespace, 16, 1),
+                token::create_token(TokenKind::Identifier, 17, 6),
+                token::create_token(TokenKind::Whitespace, 23, 1),
+                token::create_token(TokenKind::OpenBracket, 24, 1),
+                token::create_token(TokenKind::Number, 25, 1),
+                token::create_token(TokenKind::CloseBracket, 26, 1),
+                token::create_token(TokenKind::Semicolon, 27, 1),
+                token::create_token(TokenKind::Whitespace, 28, 1),
+                token::create_token(TokenKind::CloseBrace, 29, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_a_shebang_at_the_start_of_the_input() {
+        let source = "#!/usr/bin/env foo\nlet x = 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Shebang, 0, 18),
+                token::create_token(TokenKind::Whitespace, 18, 1),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 19, 3),
+                token::create_token(TokenKind::Whitespace, 22, 1),
+                token::create_token(TokenKind::Identifier, 23, 1),
+                token::create_token(TokenKind::Whitespace, 24, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 25, 1),
+                token::create_token(TokenKind::Whitespace, 26, 1),
+                token::create_token(TokenKind::Number, 27, 1),
+                token::create_token(TokenKind::Semicolon, 28, 1),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_does_not_tokenize_a_shebang_after_the_start_of_the_input() {
+        let source = " #!";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Whitespace, 0, 1),
+                token::create_token(TokenKind::Invalid, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Not), 2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_newlines_apart_from_whitespace_when_enabled() {
+        let source = "a\nb \r\n\tc";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            newline_tokens: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Newline, 1, 1),
+                token::create_token(TokenKind::Identifier, 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Newline, 4, 2),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Identifier, 7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_ternaries_correctly() {
+        let source = "a ? b : c";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Question, 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Colon, 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Identifier, 8, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_question_mark_operators_correctly() {
+        let source = "a ?? b?.c?.5";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::NullishCoalescing), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::OptionalChaining), 6, 2),
+                token::create_token(TokenKind::Identifier, 8, 1),
+                // `?.` followed by a digit isn't an optional chaining
+                token::create_token(TokenKind::Question, 9, 1),
+                token::create_token(TokenKind::Dot, 10, 1),
+                token::create_token(TokenKind::Number, 11, 1),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_tokenizes_double_quoted_identifiers_when_enabled() {
+        let source = "SELECT \"col\" FROM 't'";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            double_quoted_identifiers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 6),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Identifier, 7, 5),
+                token::create_token(TokenKind::Whitespace, 12, 1),
+                token::create_token(TokenKind::Identifier, 13, 4),
+                token::create_token(TokenKind::Whitespace, 17, 1),
+                token::create_token(TokenKind::String(StringKind::SingleQuoted), 18, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_drops_invalid_tokens_when_enabled() {
+        let source = "let @ = 1;";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            drop_invalid_tokens: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Number, 8, 1),
+                token::create_token(TokenKind::Semicolon, 9, 1),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(4, 1),
+                kind: LexerErrorKind::InvalidToken { found: '@' },
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_commas_correctly() {
+        let source = "f(a, b)";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 7);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::OpenParen, 1, 1),
+                token::create_token(TokenKind::Identifier, 2, 1),
+                token::create_token(TokenKind::Comma, 3, 1),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+                token::create_token(TokenKind::CloseParen, 6, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_colons_correctly() {
+        let source = "{x: 1}";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 6);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::OpenBrace, 0, 1),
+                token::create_token(TokenKind::Identifier, 1, 1),
+                token::create_token(TokenKind::Colon, 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Number, 4, 1),
+                token::create_token(TokenKind::CloseBrace, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_double_colons_as_two_colons() {
+        // there's no path separator token (yet), so `::`
+        // is just two colons following each other
+        let source = "a::b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Colon, 1, 1),
+                token::create_token(TokenKind::Colon, 2, 1),
+                token::create_token(TokenKind::Identifier, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_decimal_numbers_correctly() {
+        let source = "1.5";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens, &vec![token::create_token(TokenKind::Number, 0, 3)]);
+    }
+
+    #[test]
+    fn it_tokenizes_member_access_correctly() {
+        let source = "obj.x";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 3),
+                token::create_token(TokenKind::Dot, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_member_access_on_numbers_correctly() {
+        // the dot isn't followed by a digit, so it's
+        // not part of the number
+        let source = "1.field";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Number, 0, 1),
+                token::create_token(TokenKind::Dot, 1, 1),
+                token::create_token(TokenKind::Identifier, 2, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_identifiers_after_numbers_apart() {
+        // identifiers can't start with a digit
+        let source = "1abc";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Number, 0, 1),
+                token::create_token(TokenKind::Identifier, 1, 3),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_reports_identifiers_after_numbers_when_forbidden() {
+        let source = "1abc + 2.5_x2;";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            forbid_identifiers_after_numbers: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Invalid, 0, 4),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 5, 1),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Invalid, 7, 6),
+                token::create_token(TokenKind::Semicolon, 13, 1),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![
+                LexerError {
+                    span: Span::new(0, 4),
+                    kind: LexerErrorKind::InvalidNumberLiteral,
+                },
+                LexerError {
+                    span: Span::new(7, 6),
+                    kind: LexerErrorKind::InvalidNumberLiteral,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_numbers_out_of_range_when_checked() {
+        let huge_integer = "9".repeat(50);
+        let huge_decimal = format!("{}.5", "9".repeat(400));
+        let source = format!("{huge_integer} + 9223372036854775807 + 1.5 + {huge_decimal}");
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            check_number_range: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(&source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        // the spans don't change
+        assert_eq!(tokens[0], token::create_token(TokenKind::Number, 0, 50));
+        assert_eq!(
+            handler.warnings,
+            vec![
+                LexerError {
+                    span: Span::new(0, 50),
+                    kind: LexerErrorKind::NumberOutOfRange,
+                },
+                LexerError {
+                    span: Span::new(81, 402),
+                    kind: LexerErrorKind::NumberOutOfRange,
+                },
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_tokenizes_ranges_between_numbers_correctly() {
+        // the dots aren't a decimal point since they're not followed by a digit
+        let source = "1..5";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Number, 0, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Range), 1, 2),
+                token::create_token(TokenKind::Number, 3, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_ranges_between_decimal_numbers_correctly() {
+        let sources_and_tokens = [
+            (
+                "1.2..3.4",
+                vec![
+                    token::create_token(TokenKind::Number, 0, 3),
+                    token::create_token(TokenKind::Operator(OperatorKind::Range), 3, 2),
+                    token::create_token(TokenKind::Number, 5, 3),
+                ],
+            ),
+            (
+                "1..2",
+                vec![
+                    token::create_token(TokenKind::Number, 0, 1),
+                    token::create_token(TokenKind::Operator(OperatorKind::Range), 1, 2),
+                    token::create_token(TokenKind::Number, 3, 1),
+                ],
+            ),
+            // a number has a single decimal point
+            (
+                "1.2.3",
+                vec![
+                    token::create_token(TokenKind::Number, 0, 3),
+                    token::create_token(TokenKind::Dot, 3, 1),
+                    token::create_token(TokenKind::Number, 4, 1),
+                ],
+            ),
+        ];
+
+        for (source, expected_tokens) in sources_and_tokens {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(tokens, &expected_tokens);
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_inclusive_ranges_between_identifiers_correctly() {
+        let source = "a...b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::RangeInclusive), 1, 3),
+                token::create_token(TokenKind::Identifier, 4, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_keywords_with_their_kind() {
+        let source = "if x while";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::If), 0, 2),
+                token::create_token(TokenKind::Whitespace, 2, 1),
+                token::create_token(TokenKind::Identifier, 3, 1),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Keyword(KeywordKind::While), 5, 5),
+            ]
+        );
+    }
+
+    fn char_literal_options() -> LexerOptions {
+        LexerOptions {
+            char_literals: true,
+            ..LexerOptions::default()
+        }
+    }
+
+    #[test]
+    fn it_warns_about_strings_longer_than_the_maximum_length() {
+        let source = format!("let x = 'it{};\nlet y = 1;", " goes on".repeat(20));
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            max_string_length: Some(64),
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(&source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        // the text after the opening quote is lexed again
+        assert_eq!(tokens[6].kind, TokenKind::Invalid);
+        assert_eq!(tokens[7].kind, TokenKind::Identifier);
+        assert_eq!(tokens[7].text(&source), "it");
+        let last_tokens = tokens[tokens.len() - 10..]
+            .iter()
+            .map(|token| (token.kind.clone(), token.span.line))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            last_tokens,
+            vec![
+                (TokenKind::Semicolon, Some(1)),
+                (TokenKind::Whitespace, Some(1)),
+                (TokenKind::Keyword(KeywordKind::Let), Some(2)),
+                (TokenKind::Whitespace, Some(2)),
+                (TokenKind::Identifier, Some(2)),
+                (TokenKind::Whitespace, Some(2)),
+                (TokenKind::Operator(OperatorKind::Equal), Some(2)),
+                (TokenKind::Whitespace, Some(2)),
+                (TokenKind::Number, Some(2)),
+                (TokenKind::Semicolon, Some(2)),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+        assert_eq!(
+            handler.warnings,
+            vec![LexerError {
+                span: Span {
+                    line: Some(1),
+                    column: Some(9),
+                    ..Span::new(8, 1)
+                },
+                kind: LexerErrorKind::SuspiciousStringLength,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_backtick_strings_correctly() {
+        let source = "`hello` + `hello ${name}!`";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::String(StringKind::Backtick), 0, 7),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 8, 1),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::String(StringKind::Backtick), 10, 16),
+            ]
+        );
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_tokenizes_raw_strings_when_enabled() {
+        let source = r###"r"\n" + r#"say "hi""# + r"\d+""###;
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            raw_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::String(StringKind::Raw), 0, 5),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::String(StringKind::Raw), 8, 13),
+                token::create_token(TokenKind::Whitespace, 21, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 22, 1),
+                token::create_token(TokenKind::Whitespace, 23, 1),
+                token::create_token(TokenKind::String(StringKind::Raw), 24, 6),
+            ]
+        );
+
+        let options = LexerOptions::default();
+        assert_eq!(string_value(&tokens[0], source, &options), "\\n");
+        assert_eq!(string_value(&tokens[4], source, &options), "say \"hi\"");
+        assert_eq!(string_value(&tokens[8], source, &options), "\\d+");
+        assert!(handler.errors.is_empty());
+    }
+
+    #[test]
+    fn it_reports_unterminated_raw_strings() {
+        let source = "x = r#\"never \" closed";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            raw_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens.last(),
+            Some(&token::create_token(
+                TokenKind::String(StringKind::Raw),
+                4,
+                17
+            ))
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(4, 17),
+                kind: LexerErrorKind::UnterminatedString,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_strings_with_custom_delimiters() {
+        let source = "«hello» + |wor\\|ld|";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .string_delimiter('«', '»')
+            .string_delimiter('|', '|')
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::String(StringKind::Custom), 0, 9),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 10, 1),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::String(StringKind::Custom), 12, 9),
+            ]
+        );
+
+        let options = LexerOptions {
+            string_delimiters: vec![('«', '»'), ('|', '|')],
+            ..LexerOptions::default()
+        };
+        assert_eq!(string_value(&tokens[0], source, &options), "hello");
+        assert_eq!(string_value(&tokens[4], source, &options), "wor|ld");
+    }
+
+    #[test]
+    fn it_starts_custom_strings_right_after_operators() {
+        let source = "a =|x|";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .string_delimiter('|', '|')
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 2, 1),
+                token::create_token(TokenKind::String(StringKind::Custom), 3, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_char_literals_correctly() {
+        for source in ["'a'", "'\\n'", "'я'"] {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::with_options(source, &mut handler, char_literal_options());
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens,
+                &vec![token::create_token(TokenKind::Char, 0, source.len())]
+            );
+            assert!(handler.errors.is_empty());
+        }
+    }
+
+    #[test]
+    fn it_reports_char_literals_with_more_than_one_character() {
+        let source = "x = 'ab'";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::with_options(source, &mut handler, char_literal_options());
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens[4], token::create_token(TokenKind::Invalid, 4, 4));
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(4, 4),
+                kind: LexerErrorKind::InvalidCharLiteral,
+            }]
+        );
+
+        // double quotes are still strings
+        let source = "\"ab\"";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::with_options(source, &mut handler, char_literal_options());
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![token::create_token(
+                TokenKind::String(StringKind::DoubleQuoted),
+                0,
+                4
+            )]
+        );
+    }
+
+    #[test]
+    fn it_ends_the_tokens_with_an_eof_token() {
+        for source in [
+            "let greetings = 'привет мой друг';",
+            "x /* unterminated",
+            "",
+        ] {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::builder().emit_eof(true).build(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens.last(),
+                Some(&token::create_token(TokenKind::Eof, source.len(), 0))
+            );
+            assert_eq!(
+                tokens
+                    .iter()
+                    .filter(|token| token.kind == TokenKind::Eof)
+                    .count(),
+                1
+            );
+        }
+    }
+
+    #[test]
+    fn it_skips_whitespace_tokens_correctly() {
+        let source = "let x = 1;";
+
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+        assert_eq!(lexer.lex().len(), 8);
+
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            skip_whitespace: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Number, 8, 1),
+                token::create_token(TokenKind::Semicolon, 9, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_builds_a_lexer_skipping_whitespace_and_comments() {
+        let source = "let x = 1; // one\nx /* a */ >>= 2";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .skip_whitespace(true)
+            .skip_comments(true)
+            .max_operator_length(3)
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Number, 8, 1),
+                token::create_token(TokenKind::Semicolon, 9, 1),
+                token::create_token(TokenKind::Identifier, 18, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::CompoundShiftRight), 28, 3),
+                token::create_token(TokenKind::Number, 32, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_splits_operators_into_single_characters_with_a_max_length_of_zero() {
+        let source = "a == b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .max_operator_length(0)
+            .build(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 2, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 3, 1),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_does_not_mark_a_token_incomplete_when_the_last_one_is_skipped() {
+        let source = "x // trailing";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            skip_comments: true,
+            mark_incomplete_tokens: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_builds_a_lexer_with_custom_keywords() {
+        let mut keywords = HashMap::new();
+        keywords.insert(
+            String::from("foo"),
+            KeywordKind::Custom(KeywordCategory::Function),
+        );
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::builder()
+            .keywords(keywords)
+            .build("foo let", &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(
+                    TokenKind::Keyword(KeywordKind::Custom(KeywordCategory::Function)),
+                    0,
+                    3
+                ),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Identifier, 4, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_keywords_from_a_custom_keyword_list() {
+        let source = "foo let";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::with_keywords(source, &mut handler, &["foo"]);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(
+                    TokenKind::Keyword(KeywordKind::Custom(KeywordCategory::Other)),
+                    0,
+                    3
+                ),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                // the default keywords don't apply anymore
+                token::create_token(TokenKind::Identifier, 4, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_keywords_from_a_custom_keyword_map() {
+        let source = "unless x";
+        let mut handler = ErrorHandler::new();
+        let mut options = LexerOptions::default();
+        options.keywords.insert(
+            String::from("unless"),
+            KeywordKind::Custom(KeywordCategory::ControlFlow),
+        );
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(
+                    TokenKind::Keyword(KeywordKind::Custom(KeywordCategory::ControlFlow)),
+                    0,
+                    6
+                ),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Identifier, 7, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_gets_the_span_of_each_statement() {
+        let source = "let x = 1; let y = 2;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            statement_spans(tokens),
+            vec![Span::new(0, 10), Span::new(11, 10)]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_logical_and_bitwise_operators_correctly() {
+        let source = "a && b || c & d | e";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(tokens.len(), 17);
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::LogicalAnd), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::LogicalOr), 7, 2),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Identifier, 10, 1),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::BitwiseAnd), 12, 1),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Identifier, 14, 1),
+                token::create_token(TokenKind::Whitespace, 15, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::BitwiseOr), 16, 1),
+                token::create_token(TokenKind::Whitespace, 17, 1),
+                token::create_token(TokenKind::Identifier, 18, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 0);
+    }
+
+    #[test]
+    fn it_tokenizes_shift_operators_correctly() {
+        let source = "a >> b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::ShiftRight), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_bitwise_operators_correctly() {
+        // operators are at most 2 characters long,
+        // so `>>>` is split into `>>` and `>`
+        assert!(matches_pattern(
+            "a ^ ~b << c >>> d",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Operator(OperatorKind::BitwiseXor),
+                TokenKind::Operator(OperatorKind::BitwiseNot),
+                TokenKind::Identifier,
+                TokenKind::Operator(OperatorKind::ShiftLeft),
+                TokenKind::Identifier,
+                TokenKind::Operator(OperatorKind::ShiftRight),
+                TokenKind::Operator(OperatorKind::GreaterThan),
+                TokenKind::Identifier,
+            ]
+        ));
+    }
+
+    #[test]
+    fn it_remaps_token_spans() {
+        let source = "let value = 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            remap_spans(tokens, |start| start + 10),
+            vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 10, 3),
+                token::create_token(TokenKind::Whitespace, 13, 1),
+                token::create_token(TokenKind::Identifier, 14, 5),
+                token::create_token(TokenKind::Whitespace, 19, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 20, 1),
+                token::create_token(TokenKind::Whitespace, 21, 1),
+                token::create_token(TokenKind::Number, 22, 1),
+                token::create_token(TokenKind::Semicolon, 23, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_lexes_expressions() {
+        let tokens = lex_expression("1 + 2").unwrap();
+
+        assert_eq!(
+            tokens,
+            vec![
+                token::create_token(TokenKind::Number, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Add), 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Number, 4, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_rejects_statements_in_expressions() {
+        assert_eq!(
+            lex_expression("let x = 1"),
+            Err(LexerError {
+                span: Span::new(0, 3),
+                kind: LexerErrorKind::StatementInExpression,
+            })
+        );
+        assert_eq!(
+            lex_expression("x + 1;"),
+            Err(LexerError {
+                span: Span::new(5, 1),
+                kind: LexerErrorKind::StatementInExpression,
+            })
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_comparison_operators_correctly() {
+        let source = "a >= b <= c";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::GreaterThanOrEqual), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+                token::create_token(TokenKind::Whitespace, 6, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::LessThanOrEqual), 7, 2),
+                token::create_token(TokenKind::Whitespace, 9, 1),
+                token::create_token(TokenKind::Identifier, 10, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_does_not_join_comparison_operators_separated_by_whitespace() {
+        let source = "a > = b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::GreaterThan), 2, 1),
+                token::create_token(TokenKind::Whitespace, 3, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Identifier, 6, 1),
+            ]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "unicode-normalization")]
+    fn it_compares_normalized_identifiers() {
+        // é as a single code point, and as an e followed by a combining acute accent
+        let nfc_source = "let caf\u{e9} = 1;";
+        let nfd_source = "let cafe\u{301} = 1;";
+        let options = || LexerOptions {
+            normalize_identifiers: true,
+            ..LexerOptions::default()
+        };
+
+        let mut nfc_handler = ErrorHandler::new();
+        let nfc_tokens = Lexer::with_options(nfc_source, &mut nfc_handler, options()).into_tokens();
+        let mut nfd_handler = ErrorHandler::new();
+        let nfd_tokens = Lexer::with_options(nfd_source, &mut nfd_handler, options()).into_tokens();
+
+        assert!(nfc_handler.errors.is_empty());
+        assert!(nfd_handler.errors.is_empty());
+        assert_eq!(
+            nfc_tokens[2],
+            token::create_token(TokenKind::Identifier, 4, 5)
+        );
+        assert_eq!(
+            nfd_tokens[2],
+            token::create_token(TokenKind::Identifier, 4, 6)
+        );
+        assert_eq!(
+            identifier_name(&nfc_tokens[2], nfc_source, &options()),
+            identifier_name(&nfd_tokens[2], nfd_source, &options())
+        );
+
+        // without normalization the combining accent doesn't continue the identifier
+        let mut handler = ErrorHandler::new();
+        let tokens = Lexer::new(nfd_source, &mut handler).into_tokens();
+
+        assert_eq!(tokens[2], token::create_token(TokenKind::Identifier, 4, 4));
+        assert_eq!(tokens[3], token::create_token(TokenKind::Invalid, 8, 2));
+        assert_ne!(
+            identifier_name(&tokens[2], nfd_source, &LexerOptions::default()),
+            identifier_name(&nfc_tokens[2], nfc_source, &LexerOptions::default())
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_power_operator_correctly() {
+        let source = "2 ** 3";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Number, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Power), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Number, 5, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_single_stars_as_multiply_operators() {
+        assert!(matches_pattern(
+            "a * b * c",
+            &[
+                TokenKind::Identifier,
+                TokenKind::Operator(OperatorKind::Multiply),
+                TokenKind::Identifier,
+                TokenKind::Operator(OperatorKind::Multiply),
+                TokenKind::Identifier,
+            ]
+        ));
+    }
+
+    #[test]
+    fn it_checks_structural_equality_ignoring_trivia() {
+        let compact_source = "let x=1;";
+        let mut compact_handler = ErrorHandler::new();
+        let mut compact_lexer = Lexer::new(compact_source, &mut compact_handler);
+        let compact_tokens = compact_lexer.lex();
+
+        let spaced_source = "let x = 1 ;";
+        let mut spaced_handler = ErrorHandler::new();
+        let mut spaced_lexer = Lexer::new(spaced_source, &mut spaced_handler);
+        let spaced_tokens = spaced_lexer.lex();
+
+        let changed_source = "let x = 2;";
+        let mut changed_handler = ErrorHandler::new();
+        let mut changed_lexer = Lexer::new(changed_source, &mut changed_handler);
+        let changed_tokens = changed_lexer.lex();
+
+        assert!(structurally_equal(
+            compact_tokens,
+            compact_source,
+            spaced_tokens,
+            spaced_source
+        ));
+        assert!(!structurally_equal(
+            compact_tokens,
+            compact_source,
+            changed_tokens,
+            changed_source
+        ));
+    }
+
+    #[test]
+    fn it_tokenizes_fat_arrows_correctly() {
+        let source = "x => x";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::FatArrow), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 0);
+    }
+
+    #[test]
+    fn it_tokenizes_thin_arrows_correctly() {
+        let source = "a -> b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::ThinArrow), 2, 2),
+                token::create_token(TokenKind::Whitespace, 4, 1),
+                token::create_token(TokenKind::Identifier, 5, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 0);
+    }
+
+    #[test]
+    fn it_tokenizes_three_character_operators_when_enabled() {
+        let source = "a === b >>= c";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            max_operator_length: 3,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::StrictEqual), 2, 3),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Identifier, 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::CompoundShiftRight), 8, 3),
+                token::create_token(TokenKind::Whitespace, 11, 1),
+                token::create_token(TokenKind::Identifier, 12, 1),
+            ]
+        );
+        assert_eq!(handler.errors.len(), 0);
+    }
+
+    #[test]
+    fn it_splits_invalid_operators_from_their_longest_valid_prefix() {
+        let source = "a +=- b";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            max_operator_length: 3,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::Identifier, 0, 1),
+                token::create_token(TokenKind::Whitespace, 1, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::CompoundAdd), 2, 2),
+                token::create_token(TokenKind::Operator(OperatorKind::Subtract), 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Identifier, 6, 1),
+            ]
+        );
+        assert_eq!(
+            handler.errors,
+            vec![LexerError {
+                span: Span::new(2, 3),
+                kind: LexerErrorKind::InvalidOperator,
+            }]
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_strings_with_escaped_quotes_correctly() {
+        let source = "\"a\\\"b\\n\";";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![
+                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 0, 8),
+                token::create_token(TokenKind::Semicolon, 8, 1),
+            ]
+        );
+        assert_eq!(
+            string_value(&tokens[0], source, &LexerOptions::default()),
+            "a\"b\n"
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_strings_with_a_custom_escape_character() {
+        let source = "\"a^\"b\"";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            escape_character: '^',
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![token::create_token(
+                TokenKind::String(StringKind::DoubleQuoted),
+                0,
+                6
+            )]
+        );
+
+        let options = LexerOptions {
+            escape_character: '^',
+            ..LexerOptions::default()
+        };
+        assert_eq!(string_value(&tokens[0], source, &options), "a\"b");
+    }
+
+    #[test]
+    fn it_streams_diagnostics_as_they_occur() {
+        let source = "let value =+ 1;\nlet @ = 2; /* oops";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let mut diagnostics = Vec::new();
+        lexer.lex_with_diagnostics(|error| diagnostics.push(error.span));
+
+        // the lexer is still usable after streaming the diagnostics
+        let collected_spans = lexer
+            .handler()
+            .errors()
+            .iter()
+            .map(|error| error.span)
+            .collect::<Vec<_>>();
+        assert_eq!(diagnostics.len(), 3);
+        assert_eq!(diagnostics, collected_spans);
+    }
+
+    #[test]
+    fn it_streams_diagnostics_in_the_order_they_are_produced() {
+        // both diagnostics come from lexing the last string
+        let source = "'a' 'b";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            forbid_adjacent_strings: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let mut severities = Vec::new();
+        lexer.lex_with_diagnostics(|diagnostic| severities.push(diagnostic.severity()));
+
+        assert_eq!(severities, vec![Severity::Warning, Severity::Error]);
+        assert_eq!(
+            handler.warnings[0].kind,
+            LexerErrorKind::AdjacentStringLiterals
+        );
+        assert_eq!(handler.errors[0].kind, LexerErrorKind::UnterminatedString);
+    }
+
+    #[test]
+    fn it_keeps_spans_identical_on_a_large_generated_source() {
+        let unit = "let s = 'привет'; /* a */ let n = 12.5 >>= x;\n";
+        let repetitions = 500;
+        let source = unit.repeat(repetitions);
+
+        let mut unit_handler = ErrorHandler::new();
+        let mut unit_lexer = Lexer::new(unit, &mut unit_handler);
+        let unit_tokens = unit_lexer.lex();
+
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(&source, &mut handler);
+        let tokens = lexer.lex();
+
+        // the tokens of each repetition are the ones of the unit, shifted
+        assert_eq!(tokens.len(), unit_tokens.len() * repetitions);
+        for (index, token) in tokens.iter().enumerate() {
+            let unit_token = &unit_tokens[index % unit_tokens.len()];
+            let offset = index / unit_tokens.len() * unit.len();
+
+            assert_eq!(token.kind, unit_token.kind);
+            assert_eq!(token.span.start, unit_token.span.start + offset);
+            assert_eq!(token.span.length, unit_token.span.length);
+        }
+    }
+
+    #[test]
+    fn it_lexes_from_an_offset_with_the_same_spans_as_a_full_lex() {
+        let source = "let a = 1;\nlet @ = 'two';\r\nlet c = a + b; // sum";
+        let options = || LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut full_handler = ErrorHandler::new();
+        let full_tokens = Lexer::with_options(source, &mut full_handler, options()).into_tokens();
+        assert_eq!(full_handler.errors.len(), 1);
+
+        for byte_offset in [0, 11, 27, source.len()] {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::with_options(source, &mut handler, options());
+            let expected_tokens: Vec<_> = full_tokens
+                .iter()
+                .filter(|token| token.span.start >= byte_offset)
+                .cloned()
+                .collect();
+            lexer.lex();
+
+            // the same lexer can lex from the offset again,
+            // without reporting the same error twice
+            assert_eq!(lexer.lex_from(byte_offset), &expected_tokens);
+            assert_eq!(lexer.lex_from(byte_offset), &expected_tokens);
+            assert_eq!(lexer.handler().errors(), full_handler.errors());
+        }
+    }
+
+    #[test]
+    fn it_lexes_an_edited_input_from_the_edit() {
+        let source = "let a = 1;\nlet b = 2;";
+        let edited_source = "let a = 1;\nlet bc = @;";
+        let options = || LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut full_handler = ErrorHandler::new();
+        let full_tokens =
+            Lexer::with_options(edited_source, &mut full_handler, options()).into_tokens();
+
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::with_options(source, &mut handler, options());
+        lexer.lex();
+
+        // the edit starts on the second line
+        assert_eq!(lexer.lex_edited_from(edited_source, 11), &full_tokens);
+        assert_eq!(lexer.handler().errors(), full_handler.errors());
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "starts before the end of the previous one")]
+    fn it_asserts_that_tokens_are_consumed_in_order() {
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new("let value = 1;", &mut handler);
+
+        // a handler going back in the input would do this
+        lexer.consume_token_explicit(token::create_token(TokenKind::Identifier, 4, 5));
+        lexer.consume_token_explicit(token::create_token(TokenKind::Identifier, 6, 3));
     }
 
-    /**
-     * Gets a slice from the input string that represents the buffered token
-     * On a defined character index, the buffered token is the slice
-     * from the buffered_token_start to the preceding character indice
-     * (slices are exclusive on the end index)
-     */
-    fn get_buffered_token(&self) -> &str {
-        // TODO: consider if you should introduce caching here
-        // Could be a bigger of a concern when you just want to get the length of the buffered token
-        // Will see, maybe I just don't understand enough how string slices work, and I'm overthinking it 🤷
-        &self.input[self.buffered_token_start..self.current_character_byte_index]
+    #[test]
+    fn it_streams_the_same_tokens_as_lex() {
+        let sources = [
+            "let value = 1 + 3 + 4;",
+            "let name = name + ' ' + \"hey you!\";",
+            "let value =+ 1;\nlet @ = 2; // done",
+            "/* a /* nested */ comment */ x >>>= 'unterminated",
+            "",
+        ];
+
+        for source in sources {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+            let batch_tokens = lexer.lex();
+
+            let mut streamed_handler = ErrorHandler::new();
+            let streamed_tokens = Lexer::new(source, &mut streamed_handler).collect::<Vec<_>>();
+
+            assert_eq!(&streamed_tokens, batch_tokens);
+            assert_eq!(streamed_handler.errors, handler.errors);
+        }
     }
 
-    fn advance_cursor(&mut self) {
-        self.cursor += 1;
+    #[test]
+    fn it_peeks_tokens_without_consuming_them() {
+        let source = "let value =+ @;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let mut streamed_tokens = Vec::new();
+        while let Some(peeked_token) = lexer.peek_token().cloned() {
+            // peeking twice doesn't lex further
+            assert_eq!(lexer.peek_token(), Some(&peeked_token));
+            assert_eq!(lexer.next_token(), Some(peeked_token.clone()));
+            streamed_tokens.push(peeked_token);
+        }
+        assert_eq!(lexer.next_token(), None);
+
+        let (tokens, errors) = tokenize(source);
+        assert_eq!(streamed_tokens, tokens);
+        assert_eq!(handler.errors, errors);
     }
 
-    fn consume_buffered_token(&mut self) {
-        let token_kind = match &self.current_state {
-            State::InIdentifier => {
-                // if the identifier matches a keyword,
-                // consume the token as a keyword
-                let buffered_token = self.get_buffered_token();
-                if character_helpers::is_keyword(buffered_token) {
-                    TokenKind::Keyword
-                } else {
-                    TokenKind::Identifier
-                }
-            }
-            State::InString(string_state) => {
+    #[test]
+    fn it_peeks_the_tokens_left_after_a_full_lex() {
+        let source = "a + b";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+        let tokens = lexer.lex().clone();
 
-                // advance the character byte index so that the closing
-                // quote is included in the buffered token
-                self.current_character_byte_index += 1;
+        for token in &tokens {
+            assert_eq!(lexer.peek_token(), Some(token));
+            assert_eq!(lexer.next_token().as_ref(), Some(token));
+        }
+        assert_eq!(lexer.peek_token(), None);
+    }
 
-                match string_state {
-                    StringState::InSingleQuote => {
-                        TokenKind::String(StringKind::SingleQuoted)
-                    }
-                    StringState::InDoubleQuote => {
-                        TokenKind::String(StringKind::DoubleQuoted)
-                    }
-                }
-            },
-            State::InNumber => TokenKind::Number,
-            State::InOperator => {
-                let buffered_token = self.get_buffered_token();
-                let operator_kind = token::match_operator_slice_to_operator_kind(buffered_token);
-                // if it's doesn't match any valid operator, it's a compound-like operator
-                // We should split the operator in two, consume the first
-                // part and then reprocess the second part
-                match operator_kind {
-                    OperatorKind::Invalid => {
-                        self.handler.add_error(LexerError {
-                            span: self.create_current_token_span(),
-                            kind: LexerErrorKind::InvalidOperator,
-                        });
-                        let buffered_token= self.get_buffered_token();
-                        let first_operator_slice = &buffered_token[0..1];
-                        let first_operator_kind = token::match_operator_slice_to_operator_kind(first_operator_slice);
+    #[test]
+    fn it_streams_tokens_one_by_one() {
+        let source = "let value = 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
 
-                        let first_token = token::create_token( TokenKind::Operator(first_operator_kind), self.buffered_token_start, 1);
-                        self.consume_token_explicit(first_token);
+        assert_eq!(
+            lexer.next_token(),
+            Some(token::create_token(
+                TokenKind::Keyword(KeywordKind::Let),
+                0,
+                3
+            ))
+        );
+        assert_eq!(
+            lexer.next_token(),
+            Some(token::create_token(TokenKind::Whitespace, 3, 1))
+        );
 
-                        self.buffered_token_start += 1;
+        let mut kinds = Vec::new();
+        for token in lexer {
+            kinds.push(token.kind);
+        }
+        assert_eq!(
+            kinds,
+            vec![
+                TokenKind::Identifier,
+                TokenKind::Whitespace,
+                TokenKind::Operator(OperatorKind::Equal),
+                TokenKind::Whitespace,
+                TokenKind::Number,
+                TokenKind::Semicolon,
+            ]
+        );
+    }
 
-                        let buffered_token= self.get_buffered_token();
-                        let second_operator_slice = &buffered_token[0..1];
-                        let second_operator_kind = token::match_operator_slice_to_operator_kind(second_operator_slice);
-                        TokenKind::Operator(second_operator_kind)
-                    },
-                    _ => TokenKind::Operator(operator_kind),
-                }
-            },
-            // NOTE: this arm will never be matched
-            // it's a bug if it does
-            State::Start => unreachable!("This function should never be called to buffer tokens when the lexer is in a `Start` state. Use `consume_token_explicit`"),
-        };
+    #[test]
+    fn it_streams_the_tokens_left_after_a_full_lex() {
+        let source = "let value = 1;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+        let tokens = lexer.lex().clone();
 
-        let token = Token {
-            kind: token_kind,
-            span: self.create_current_token_span(),
-        };
+        assert_eq!(lexer.next_token(), Some(tokens[0].clone()));
+        assert_eq!(lexer.next_token(), Some(tokens[1].clone()));
+        // the tokens handed out aren't returned again
+        assert_eq!(lexer.lex(), &tokens[2..]);
+        assert_eq!(lexer.collect::<Vec<_>>(), &tokens[2..]);
+    }
 
-        // the cursor is one character ahead of the last character
-        // of the token
-        // so the the start of the next token is the current cursor position
-        self.tokens.push(token);
+    #[test]
+    fn it_returns_ok_when_lexing_a_clean_source_checked() {
+        let mut handler = ErrorHandler::new();
+        let lexer = Lexer::new("let value = 1;", &mut handler);
+
+        let tokens = lexer.lex_checked().unwrap();
+
+        assert_eq!(tokens.len(), 8);
     }
 
-    /**
-     * Helper method to consume a token created on the fly
-     * The cursor would likely need to to incremented
-     * as the character/string would've been used to
-     * create the token
-     */
-    fn consume_token_explicit(&mut self, token: Token) {
-        self.tokens.push(token);
+    #[test]
+    fn it_returns_the_errors_when_lexing_an_invalid_source_checked() {
+        let mut handler = ErrorHandler::new();
+        let lexer = Lexer::new("let value =+ 1;", &mut handler);
+
+        let errors = lexer.lex_checked().unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![LexerError {
+                span: Span::new(10, 2),
+                kind: LexerErrorKind::InvalidOperator,
+            }]
+        );
     }
-}
 
-// TODO: consider snapshot testing instead of fixtures
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use similar_asserts::assert_eq;
+    #[test]
+    fn it_clones_tokens_correctly() {
+        let source = "let word = \"Hello\";";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+        let cloned_tokens = tokens.clone();
+
+        assert_eq!(&cloned_tokens, tokens);
+
+        let span = cloned_tokens[6].span;
+        assert_eq!(span, tokens[6].span);
+        assert_eq!(&source[span.start..span.start + span.length], "\"Hello\"");
+    }
 
     #[test]
-    fn it_tokenizes_basic_number_assignment_correctly() {
-        let source = String::from("let value = 1;");
+    fn it_counts_token_kinds_in_a_map() {
+        let source = "let word = \"Hello\" + \" \" + \"world!\"; ";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        // assert_eq!(tokens.len(), 8);
+        let mut frequencies = HashMap::new();
+        for token in tokens {
+            *frequencies.entry(token.kind.clone()).or_insert(0) += 1;
+        }
+
+        assert_eq!(frequencies[&TokenKind::Whitespace], 8);
+        assert_eq!(frequencies[&TokenKind::String(StringKind::DoubleQuoted)], 3);
+        assert_eq!(frequencies[&TokenKind::Operator(OperatorKind::Add)], 2);
+        assert_eq!(frequencies.get(&TokenKind::Number), None);
+    }
+
+    #[test]
+    fn it_keeps_the_tokens_after_the_lexer_is_dropped() {
+        let source = "let value = 1;";
+        let mut handler = ErrorHandler::new();
+
+        let tokens = {
+            let lexer = Lexer::new(source, &mut handler);
+            lexer.into_tokens()
+        };
+
+        assert!(handler.errors.is_empty());
         assert_eq!(
             tokens,
-            &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
+            vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
                 token::create_token(TokenKind::Whitespace, 3, 1),
                 token::create_token(TokenKind::Identifier, 4, 5),
                 token::create_token(TokenKind::Whitespace, 9, 1),
@@ -389,382 +5016,734 @@ mod tests {
     }
 
     #[test]
-    fn it_tokenizes_number_compound_assignment_correctly() {
-        let source = String::from("let value += 1;");
+    fn it_displays_token_kinds_correctly() {
+        assert_eq!(OperatorKind::Add.to_string(), "+");
+        assert_eq!(OperatorKind::UnsignedShiftRight.to_string(), ">>>");
+        assert_eq!(StringKind::DoubleQuoted.to_string(), "double-quoted");
+        assert_eq!(
+            TokenKind::String(StringKind::DoubleQuoted).to_string(),
+            "string (double-quoted)"
+        );
+        assert_eq!(
+            TokenKind::Operator(OperatorKind::CompoundAdd).to_string(),
+            "operator `+=`"
+        );
+        assert_eq!(
+            TokenKind::Keyword(KeywordKind::If).to_string(),
+            "keyword (if)"
+        );
+        assert_eq!(
+            TokenKind::Keyword(KeywordKind::Custom(KeywordCategory::ControlFlow)).to_string(),
+            "keyword (control flow)"
+        );
+        assert_eq!(TokenKind::OpenBrace.to_string(), "`{`");
+        assert_eq!(
+            TokenKind::BlockComment { max_depth: 2 }.to_string(),
+            "block comment"
+        );
+    }
+
+    #[test]
+    fn it_displays_errors_correctly() {
+        let invalid_operator = LexerError {
+            span: Span::new(10, 2),
+            kind: LexerErrorKind::InvalidOperator,
+        };
+        let invalid_token = LexerError {
+            span: Span::new(4, 1),
+            kind: LexerErrorKind::InvalidToken { found: '@' },
+        };
+
+        assert_eq!(
+            invalid_operator.to_string(),
+            "invalid operator at bytes 10..12"
+        );
+        assert_eq!(invalid_token.to_string(), "invalid token at bytes 4..5");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn it_round_trips_tokens_through_json() {
+        let source = "let name = \"привет\" + 1; /* done */";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let options = LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let lexer = Lexer::with_options(source, &mut handler, options);
+        let tokens = lexer.into_tokens();
 
-        let tokens = lexer.lex();
+        let json = serde_json::to_string(&tokens).unwrap();
+        let deserialized_tokens: Vec<Token> = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(deserialized_tokens, tokens);
+        assert!(json.contains(r#"{"Operator":"Add"}"#));
+    }
+
+    #[test]
+    fn it_renders_error_snippets_correctly() {
+        let source = "let x = 1;\nlet value =+ 1;\nlet привет = @";
+        let mut handler = ErrorHandler::new();
+        let lexer = Lexer::new(source, &mut handler);
+
+        let errors = lexer.lex_checked().unwrap_err();
+
+        assert_eq!(errors[0].snippet(source), "let value =+ 1;\n          ^^");
+        // the column is counted in characters, not bytes
+        assert_eq!(errors[1].snippet(source), "let привет = @\n             ^");
+
+        let at_start = LexerError {
+            span: Span::new(0, 3),
+            kind: LexerErrorKind::InvalidOperator,
+        };
+        assert_eq!(at_start.snippet(source), "let x = 1;\n^^^");
+
+        let at_end = LexerError {
+            span: Span::new(source.len(), 0),
+            kind: LexerErrorKind::TooManyErrors,
+        };
+        assert_eq!(at_end.snippet(source), "let привет = @\n              ^");
+
+        let over_multibyte_character = LexerError {
+            span: Span::new(31, 12),
+            kind: LexerErrorKind::InvalidToken { found: 'п' },
+        };
+        assert_eq!(
+            over_multibyte_character.snippet(source),
+            "let привет = @\n    ^^^^^^"
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_with_the_convenience_function() {
+        let (tokens, errors) = tokenize("let x = 1;");
 
         assert_eq!(tokens.len(), 8);
+        assert!(errors.is_empty());
+
+        let (tokens, errors) = tokenize("let x =+ 1;");
+
+        assert_eq!(tokens.len(), 9);
+        assert_eq!(errors.len(), 1);
+    }
+
+    #[test]
+    fn it_lexes_a_file_correctly() {
+        let path = std::env::temp_dir().join(format!("simple-lexer-{}.txt", std::process::id()));
+        std::fs::write(&path, "let x = @;").unwrap();
+
+        let result = lex_file(&path);
+        std::fs::remove_file(&path).unwrap();
+
+        let (tokens, errors) = result.unwrap();
         assert_eq!(
             tokens,
-            &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
+            vec![
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
                 token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::CompoundAdd), 10, 2),
-                token::create_token(TokenKind::Whitespace, 12, 1),
-                token::create_token(TokenKind::Number, 13, 1),
-                token::create_token(TokenKind::Semicolon, 14, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Invalid, 8, 1),
+                token::create_token(TokenKind::Semicolon, 9, 1),
             ]
         );
+        assert_eq!(errors.len(), 1);
+
+        let missing_path = std::env::temp_dir().join("simple-lexer-missing.txt");
+        assert!(lex_file(missing_path).is_err());
     }
 
     #[test]
-    fn it_tokenizes_invalid_operator_correctly_1() {
-        let source = String::from("let value =+ 1;");
+    fn it_returns_tokens_when_lexing_without_errors() {
+        let tokens = lex_and_report("let value = 1;").unwrap();
+
+        assert_eq!(tokens.len(), 8);
+    }
+
+    #[test]
+    fn it_reports_all_errors_when_lexing_with_errors() {
+        let report = lex_and_report("let value =+ 1;\nlet @ = 2;").unwrap_err();
+
+        assert_eq!(
+            report,
+            [
+                "error: invalid operator at 1:11",
+                "  |",
+                "1 | let value =+ 1;",
+                "  |           ^^",
+                "",
+                "error: invalid token at 2:5",
+                "  |",
+                "2 | let @ = 2;",
+                "  |     ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn it_tokenizes_booleans_correctly() {
+        let source = "let b = true;";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 9);
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
                 token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Add), 11, 1),
-                token::create_token(TokenKind::Whitespace, 12, 1),
-                token::create_token(TokenKind::Number, 13, 1),
-                token::create_token(TokenKind::Semicolon, 14, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Boolean, 8, 4),
+                token::create_token(TokenKind::Semicolon, 12, 1),
             ]
         );
+        assert!(matches_pattern("false", &[TokenKind::Boolean]));
     }
 
     #[test]
-    fn it_tokenizes_invalid_operator_correctly_2() {
-        let source = String::from("let value %=+ 1;");
+    fn it_tokenizes_null_correctly() {
+        let source = "let x = null;";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 9);
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
                 token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::CompoundModulo), 10, 2),
-                token::create_token(TokenKind::Operator(OperatorKind::Add), 12, 1),
-                token::create_token(TokenKind::Whitespace, 13, 1),
-                token::create_token(TokenKind::Number, 14, 1),
-                token::create_token(TokenKind::Semicolon, 15, 1),
+                token::create_token(TokenKind::Identifier, 4, 1),
+                token::create_token(TokenKind::Whitespace, 5, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 1),
+                token::create_token(TokenKind::Null, 8, 4),
+                token::create_token(TokenKind::Semicolon, 12, 1),
             ]
         );
+        assert!(matches_pattern("nil", &[TokenKind::Null]));
+        assert!(matches_pattern("nullable", &[TokenKind::Identifier]));
     }
 
     #[test]
-    fn it_tokenizes_invalid_operator_correctly_3() {
-        let source = String::from("let value ++++ 1;");
+    fn it_tracks_the_maximum_depth_of_block_comments() {
+        let source = "/* a /* b */ c */ d";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 9);
+        // the comment closes at the outer delimiter
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Increment), 10, 2),
-                token::create_token(TokenKind::Operator(OperatorKind::Increment), 12, 2),
-                token::create_token(TokenKind::Whitespace, 14, 1),
-                token::create_token(TokenKind::Number, 15, 1),
-                token::create_token(TokenKind::Semicolon, 16, 1),
+                token::create_token(TokenKind::BlockComment { max_depth: 2 }, 0, 17),
+                token::create_token(TokenKind::Whitespace, 17, 1),
+                token::create_token(TokenKind::Identifier, 18, 1),
             ]
         );
     }
 
     #[test]
-    fn it_tokenizes_number_post_increment_correctly() {
-        let source = String::from("let value = 1;\nvalue++;");
-        let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+    fn it_keeps_the_maximum_depth_of_block_comments_after_closing_nested_ones() {
+        let source = "/* a /* b */ c /* d /* e */ */ */";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(
+            tokens,
+            &vec![token::create_token(
+                TokenKind::BlockComment { max_depth: 3 },
+                0,
+                33
+            )]
+        );
+    }
+
+    #[test]
+    fn it_finds_mixed_quotes_in_expressions() {
+        let source = "let x = \"a\" + 'b'; let y = 'c' + 'd';";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(mixed_quotes_in_expression(tokens), vec![Span::new(14, 3)]);
+    }
+
+    #[test]
+    fn it_keeps_the_positions_of_mixed_quotes() {
+        let source = "let x = \"a\" +\n'b';";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+        let tokens = lexer.lex();
+
+        assert_eq!(mixed_quotes_in_expression(tokens), vec![tokens[10].span]);
+        assert_eq!(tokens[10].span.line, Some(2));
+    }
+
+    #[test]
+    fn it_warns_on_mixed_quotes_when_enabled() {
+        let sources_and_warnings = [
+            (
+                "'a' + \"b\";",
+                vec![LexerError {
+                    span: Span::new(6, 3),
+                    kind: LexerErrorKind::MixedQuotes,
+                }],
+            ),
+            ("'a' + 'b'; \"c\" + \"d\";", vec![]),
+        ];
 
-        let tokens = lexer.lex();
+        for (source, warnings) in sources_and_warnings {
+            let mut handler = ErrorHandler::new();
+            let options = LexerOptions {
+                warn_mixed_quotes: true,
+                ..LexerOptions::default()
+            };
+            let lexer = Lexer::with_options(source, &mut handler, options);
+            let tokens = lexer.into_tokens();
 
-        assert_eq!(tokens.len(), 12);
-        assert_eq!(
-            tokens,
-            &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
-                token::create_token(TokenKind::Whitespace, 11, 1),
-                token::create_token(TokenKind::Number, 12, 1),
-                token::create_token(TokenKind::Semicolon, 13, 1),
-                token::create_token(TokenKind::Whitespace, 14, 1),
-                token::create_token(TokenKind::Identifier, 15, 5),
-                token::create_token(TokenKind::Operator(OperatorKind::Increment), 20, 2),
-                token::create_token(TokenKind::Semicolon, 22, 1),
-            ]
-        );
+            assert_eq!(handler.warnings, warnings);
+            assert_eq!(
+                mixed_quotes_in_expression(&tokens),
+                warnings
+                    .iter()
+                    .map(|warning| warning.span)
+                    .collect::<Vec<_>>()
+            );
+        }
     }
 
     #[test]
-    fn it_tokenizes_cyrillic_strings_correctly() {
-        let source = String::from("let greetings = 'привет мой друг';");
+    fn it_coalesces_consecutive_whitespace() {
+        let source = "let   x\t\n y";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 8);
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 9),
-                token::create_token(TokenKind::Whitespace, 13, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 14, 1),
-                token::create_token(TokenKind::Whitespace, 15, 1),
-                token::create_token(TokenKind::String(StringKind::SingleQuoted), 16, 30),
-                token::create_token(TokenKind::Semicolon, 46, 1),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
+                token::create_token(TokenKind::Whitespace, 3, 3),
+                token::create_token(TokenKind::Identifier, 6, 1),
+                token::create_token(TokenKind::Whitespace, 7, 3),
+                token::create_token(TokenKind::Identifier, 10, 1),
             ]
         );
     }
 
     #[test]
-    fn it_tokenizes_source_with_string_concat_correctly() {
-        let source = String::from("let word = \"Hello\" + \" \" + \"world!\"; ");
+    fn it_tokenizes_cyrillic_identifiers_correctly() {
+        let source = "let переменная = 1;";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 17);
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
+                token::create_token(TokenKind::Keyword(KeywordKind::Let), 0, 3),
                 token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 4),
-                token::create_token(TokenKind::Whitespace, 8, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 9, 1),
-                token::create_token(TokenKind::Whitespace, 10, 1),
-                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 11, 7),
-                token::create_token(TokenKind::Whitespace, 18, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Add), 19, 1),
-                token::create_token(TokenKind::Whitespace, 20, 1),
-                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 21, 3),
+                token::create_token(TokenKind::Identifier, 4, 20),
                 token::create_token(TokenKind::Whitespace, 24, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Add), 25, 1),
+                token::create_token(TokenKind::Operator(OperatorKind::Equal), 25, 1),
                 token::create_token(TokenKind::Whitespace, 26, 1),
-                token::create_token(TokenKind::String(StringKind::DoubleQuoted), 27, 8),
-                token::create_token(TokenKind::Semicolon, 35, 1),
-                token::create_token(TokenKind::Whitespace, 36, 1),
+                token::create_token(TokenKind::Number, 27, 1),
+                token::create_token(TokenKind::Semicolon, 28, 1),
             ]
         );
+        assert_eq!(handler.errors.len(), 0);
     }
 
     #[test]
-    fn it_correctly_tokenizes_source_with_invalid_tokens() {
-        let source = String::from("let @$` = &&| something something;");
+    fn it_tokenizes_accented_identifiers_correctly() {
+        let source = "café_2 = été";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 16);
-
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Invalid, 4, 1),
-                token::create_token(TokenKind::Invalid, 5, 1),
-                token::create_token(TokenKind::Invalid, 6, 1),
+                token::create_token(TokenKind::Identifier, 0, 7),
                 token::create_token(TokenKind::Whitespace, 7, 1),
                 token::create_token(TokenKind::Operator(OperatorKind::Equal), 8, 1),
                 token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Invalid, 10, 1),
-                token::create_token(TokenKind::Invalid, 11, 1),
-                token::create_token(TokenKind::Invalid, 12, 1),
-                token::create_token(TokenKind::Whitespace, 13, 1),
-                token::create_token(TokenKind::Identifier, 14, 9),
-                token::create_token(TokenKind::Whitespace, 23, 1),
-                token::create_token(TokenKind::Identifier, 24, 9),
-                token::create_token(TokenKind::Semicolon, 33, 1),
+                token::create_token(TokenKind::Identifier, 10, 5),
             ]
-        )
+        );
+        assert_eq!(handler.errors.len(), 0);
     }
 
     #[test]
-    fn it_collects_expected_errors() {
-        let source = String::from("let value =+ 1;\nlet @$` = &&| something something;");
+    fn it_marks_tokens_at_the_end_of_the_input_as_incomplete_when_enabled() {
+        let sources_and_last_tokens = [
+            (
+                "let value = another_value",
+                token::create_token(TokenKind::Identifier, 12, 13),
+            ),
+            (
+                "let value = 'unterminated",
+                token::create_token(TokenKind::String(StringKind::SingleQuoted), 12, 13),
+            ),
+            (
+                "let value = /* unterminated",
+                token::create_token(TokenKind::BlockComment { max_depth: 1 }, 12, 15),
+            ),
+        ];
+
+        for (source, last_token) in sources_and_last_tokens {
+            let mut handler = ErrorHandler::new();
+            let options = LexerOptions {
+                mark_incomplete_tokens: true,
+                ..LexerOptions::default()
+            };
+            let mut lexer = Lexer::with_options(source, &mut handler, options);
+
+            let tokens = lexer.lex();
+
+            let expected_last_token = Token {
+                incomplete: true,
+                ..last_token
+            };
+            assert_eq!(tokens.last(), Some(&expected_last_token));
+            assert!(tokens[..tokens.len() - 1]
+                .iter()
+                .all(|token| !token.incomplete));
+        }
+    }
+
+    #[test]
+    fn it_does_not_mark_tokens_as_incomplete_by_default() {
+        let source = "let value = another_value";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        assert!(tokens.iter().all(|token| !token.incomplete));
+    }
+
+    #[test]
+    fn it_gets_the_line_and_column_of_tokens() {
+        let source = "let value = 1;\n  привет + x;";
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+
+        let tokens = lexer.lex();
+
+        // let
+        assert_eq!(tokens[0].span.line_col(source), (1, 1));
+        // 1
+        assert_eq!(tokens[6].span.line_col(source), (1, 13));
+        // привет, after the newline and the indentation
+        assert_eq!(tokens[9].span.line_col(source), (2, 3));
+        // x, columns are counted in characters
+        assert_eq!(tokens[13].span.line_col(source), (2, 12));
+    }
+
+    #[test]
+    fn it_sets_the_line_and_column_of_spans_when_tracking_positions() {
+        let source = "let value = 1;\n  привет @ \"a\\\nb\" x";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
 
         let tokens = lexer.lex();
-        assert_eq!(tokens.len(), 26);
 
+        let positions = tokens
+            .iter()
+            .map(|token| (token.span.line, token.span.column))
+            .collect::<Vec<_>>();
         assert_eq!(
-            tokens,
-            &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Add), 11, 1),
-                token::create_token(TokenKind::Whitespace, 12, 1),
-                token::create_token(TokenKind::Number, 13, 1),
-                token::create_token(TokenKind::Semicolon, 14, 1),
-                token::create_token(TokenKind::Whitespace, 15, 1),
-                token::create_token(TokenKind::Keyword, 16, 3),
-                token::create_token(TokenKind::Whitespace, 19, 1),
-                token::create_token(TokenKind::Invalid, 20, 1),
-                token::create_token(TokenKind::Invalid, 21, 1),
-                token::create_token(TokenKind::Invalid, 22, 1),
-                token::create_token(TokenKind::Whitespace, 23, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 24, 1),
-                token::create_token(TokenKind::Whitespace, 25, 1),
-                token::create_token(TokenKind::Invalid, 26, 1),
-                token::create_token(TokenKind::Invalid, 27, 1),
-                token::create_token(TokenKind::Invalid, 28, 1),
-                token::create_token(TokenKind::Whitespace, 29, 1),
-                token::create_token(TokenKind::Identifier, 30, 9),
-                token::create_token(TokenKind::Whitespace, 39, 1),
-                token::create_token(TokenKind::Identifier, 40, 9),
-                token::create_token(TokenKind::Semicolon, 49, 1),
+            positions,
+            vec![
+                (Some(1), Some(1)),
+                (Some(1), Some(4)),
+                (Some(1), Some(5)),
+                (Some(1), Some(10)),
+                (Some(1), Some(11)),
+                (Some(1), Some(12)),
+                (Some(1), Some(13)),
+                (Some(1), Some(14)),
+                (Some(1), Some(15)),
+                (Some(2), Some(3)),
+                (Some(2), Some(9)),
+                (Some(2), Some(10)),
+                (Some(2), Some(11)),
+                (Some(2), Some(12)),
+                // the escaped newline in the string is a line break too
+                (Some(3), Some(3)),
+                (Some(3), Some(4)),
             ]
         );
+        assert_eq!(handler.errors[0].span.line, Some(2));
+        assert_eq!(handler.errors[0].span.column, Some(10));
+    }
 
-        assert_eq!(handler.errors.len(), 7);
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 10,
-                    length: 2,
-                },
-                kind: LexerErrorKind::InvalidOperator,
-            },
-            handler.errors[0]
-        );
+    #[test]
+    fn it_counts_crlf_as_a_single_line_break() {
+        let source = "a\r\nb\rc\n\r\nd";
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
 
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 20,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[1]
-        );
+        let tokens = lexer.lex();
 
+        let identifier_positions = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Identifier)
+            .map(|token| {
+                assert_eq!(
+                    token.span.line_col(source),
+                    (token.span.line.unwrap(), token.span.column.unwrap())
+                );
+                (token.span.line, token.span.column)
+            })
+            .collect::<Vec<_>>();
         assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 21,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[2]
+            identifier_positions,
+            vec![
+                (Some(1), Some(1)),
+                (Some(2), Some(1)),
+                (Some(3), Some(1)),
+                (Some(5), Some(1)),
+            ]
         );
+    }
 
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 22,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[3]
-        );
+    #[test]
+    fn it_produces_the_same_byte_spans_with_and_without_position_tracking() {
+        let source = "let value = 1;\nlet other = 'two';\n\n/* a\nb */ value += other;";
 
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 26,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[4]
-        );
+        let mut handler = ErrorHandler::new();
+        let mut lexer = Lexer::new(source, &mut handler);
+        let untracked_spans = lexer
+            .lex()
+            .iter()
+            .map(|token| (token.span.start, token.span.length, token.span.line))
+            .collect::<Vec<_>>();
 
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 27,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[5]
-        );
+        let mut handler = ErrorHandler::new();
+        let options = LexerOptions {
+            track_positions: true,
+            ..LexerOptions::default()
+        };
+        let mut lexer = Lexer::with_options(source, &mut handler, options);
+        let tracked_spans = lexer
+            .lex()
+            .iter()
+            .map(|token| (token.span.start, token.span.length, token.span.line))
+            .collect::<Vec<_>>();
 
-        assert_eq!(
-            LexerError {
-                span: Span {
-                    start: 28,
-                    length: 1,
-                },
-                kind: LexerErrorKind::InvalidToken,
-            },
-            handler.errors[6]
-        );
+        assert_eq!(untracked_spans.len(), tracked_spans.len());
+        for (untracked_span, tracked_span) in untracked_spans.iter().zip(&tracked_spans) {
+            assert_eq!(untracked_span.0, tracked_span.0);
+            assert_eq!(untracked_span.1, tracked_span.1);
+            assert_eq!(untracked_span.2, None);
+            assert!(tracked_span.2.is_some());
+        }
     }
 
     #[test]
-    fn it_correctly_tokenizes_source_when_lexer_state_machine_ends_in_a_non_start_state() {
-        // Here the lexer's state machine will end in a non-start state
-        // more precisely in the InIdentifier state
-        // That's because the identifier is at the end of the source
-        // and its corresponding handler will only consume the buffered
-        // token when it encounters a non-identifier character,
-        // which doesn't happen in this case
-        // It requires special handling (to consume the buffered token when
-        // lexing ends in a non-start state), but this makes the
-        // handlers' code much simpler
-        let source = String::from("let value = another_value");
+    fn it_tokenizes_identifiers_starting_with_an_underscore() {
+        for identifier in ["_", "_x", "__name__"] {
+            let source = identifier;
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens,
+                &vec![token::create_token(
+                    TokenKind::Identifier,
+                    0,
+                    identifier.len()
+                )]
+            );
+            assert_eq!(handler.errors.len(), 0);
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_every_default_keyword_as_a_keyword() {
+        let keywords = [
+            ("let", KeywordKind::Let),
+            ("const", KeywordKind::Const),
+            ("if", KeywordKind::If),
+            ("else", KeywordKind::Else),
+            ("while", KeywordKind::While),
+            ("for", KeywordKind::For),
+            ("function", KeywordKind::Function),
+            ("mmk", KeywordKind::Mmk),
+        ];
+        for (keyword, keyword_kind) in keywords {
+            let source = keyword;
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+
+            let tokens = lexer.lex();
+
+            assert_eq!(
+                tokens,
+                &vec![token::create_token(
+                    TokenKind::Keyword(keyword_kind),
+                    0,
+                    keyword.len()
+                )]
+            );
+        }
+    }
+
+    #[test]
+    fn it_tokenizes_keywords_from_a_large_keyword_set() {
+        let source = "keyword4242 keyword_4242";
         let mut handler = ErrorHandler::new();
-        let mut lexer = Lexer::new(&source, &mut handler);
+        let mut lexer = Lexer::with_options(source, &mut handler, large_keyword_set_options());
 
         let tokens = lexer.lex();
 
-        assert_eq!(tokens.len(), 7);
-
         assert_eq!(
             tokens,
             &vec![
-                token::create_token(TokenKind::Keyword, 0, 3),
-                token::create_token(TokenKind::Whitespace, 3, 1),
-                token::create_token(TokenKind::Identifier, 4, 5),
-                token::create_token(TokenKind::Whitespace, 9, 1),
-                token::create_token(TokenKind::Operator(OperatorKind::Equal), 10, 1),
+                token::create_token(
+                    TokenKind::Keyword(KeywordKind::Custom(KeywordCategory::Other)),
+                    0,
+                    11
+                ),
                 token::create_token(TokenKind::Whitespace, 11, 1),
-                token::create_token(TokenKind::Identifier, 12, 13),
+                token::create_token(TokenKind::Identifier, 12, 12),
             ]
-        )
+        );
+    }
+
+    fn large_keyword_set_options() -> LexerOptions {
+        let mut options = LexerOptions::default();
+        for index in 0..10_000 {
+            options.keywords.insert(
+                format!("keyword{index}"),
+                KeywordKind::Custom(KeywordCategory::Other),
+            );
+        }
+        options
+    }
+
+    fn keyword_heavy_source() -> String {
+        "let value = keyword42 + some_identifier; if value while other_value;".repeat(2_000)
     }
 
     #[bench]
-    fn test_bench(b: &mut test::Bencher) {
+    fn bench_keyword_lookup_with_default_keywords(b: &mut test::Bencher) {
+        let source = keyword_heavy_source();
+        b.iter(|| {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(&source, &mut handler);
+            let _tokens = lexer.lex();
+        });
+    }
+
+    #[bench]
+    fn bench_keyword_lookup_with_a_large_keyword_set(b: &mut test::Bencher) {
+        let source = keyword_heavy_source();
+        let mut handler = ErrorHandler::new();
+        // the keyword set is built once, and the lexer
+        // is reset to lex the source again on each run
+        let mut lexer = Lexer::with_options(&source, &mut handler, large_keyword_set_options());
+        b.iter(|| {
+            lexer.reset(&source);
+            let _tokens = lexer.lex();
+        });
+    }
+
+    fn multiline_source() -> String {
+        "let value = 1;\n    let name = name + ' ' + \"hey you!\";\n".repeat(2_000)
+    }
+
+    #[bench]
+    fn bench_lexing_without_position_tracking(b: &mut test::Bencher) {
+        let source = multiline_source();
+        b.iter(|| {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(&source, &mut handler);
+            let _tokens = lexer.lex();
+        });
+    }
+
+    #[bench]
+    fn bench_lexing_with_position_tracking(b: &mut test::Bencher) {
+        let source = multiline_source();
+        b.iter(|| {
+            let mut handler = ErrorHandler::new();
+            let options = LexerOptions {
+                track_positions: true,
+                ..LexerOptions::default()
+            };
+            let mut lexer = Lexer::with_options(&source, &mut handler, options);
+            let _tokens = lexer.lex();
+        });
+    }
+
+    // the length of the buffered token is checked on every operator character
+    #[bench]
+    fn bench_lexing_an_operator_heavy_source(b: &mut test::Bencher) {
+        let source = "a += b -= c *= d /= e %= f ** g != h >= i && j || k;\n".repeat(2_000);
+        b.iter(|| {
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(&source, &mut handler);
+            let _tokens = lexer.lex();
+        });
+    }
+
+    // long tokens are where revisiting characters would hurt the most
+    fn large_generated_source() -> String {
+        let long_string = "a".repeat(1_000);
+        let long_number = "9".repeat(1_000);
+        format!("let s = '{long_string}'; /* {long_string} */ let n = {long_number};\n").repeat(200)
+    }
+
+    #[bench]
+    fn bench_lexing_a_large_generated_source(b: &mut test::Bencher) {
+        let source = large_generated_source();
         b.iter(|| {
-            let source = String::from("let value = 1;let value = 1;let value = 1;let value = 1;");
             let mut handler = ErrorHandler::new();
             let mut lexer = Lexer::new(&source, &mut handler);
             let _tokens = lexer.lex();
         });
     }
+
+    #[bench]
+    fn test_bench(b: &mut test::Bencher) {
+        b.iter(|| {
+            let source = "let value = 1;let value = 1;let value = 1;let value = 1;";
+            let mut handler = ErrorHandler::new();
+            let mut lexer = Lexer::new(source, &mut handler);
+            let _tokens = lexer.lex();
+        });
+    }
 }
@@ -1,10 +1,9 @@
-pub fn is_keyword(str: &str) -> bool {
-    // mmk is a special keyword, it's my name abbreviated
-    // not sure what to do with it rn
-    matches!(
-        str,
-        "let" | "const" | "if" | "else" | "while" | "for" | "function" | "mmk"
-    )
+pub fn is_boolean(str: &str) -> bool {
+    matches!(str, "true" | "false")
+}
+
+pub fn is_null(str: &str) -> bool {
+    matches!(str, "null" | "nil")
 }
 
 pub fn is_digit(char: char) -> bool {
@@ -12,12 +11,14 @@ pub fn is_digit(char: char) -> bool {
 }
 
 pub fn is_letter(char: char) -> bool {
-    char.is_ascii_alphabetic()
+    char.is_alphabetic()
 }
 
-// no bitwise or logical stuff for now
 pub fn is_operator(char: char) -> bool {
-    matches!(char, '+' | '-' | '*' | '/' | '=' | '!' | '<' | '>' | '%')
+    matches!(
+        char,
+        '+' | '-' | '*' | '/' | '=' | '!' | '<' | '>' | '%' | '&' | '|' | '^' | '~'
+    )
 }
 
 pub fn is_single_quote(char: char) -> bool {
@@ -28,14 +29,90 @@ pub fn is_double_quote(char: char) -> bool {
     char == '\"'
 }
 
+pub fn is_backtick(char: char) -> bool {
+    char == '`'
+}
+
+pub fn is_slash(char: char) -> bool {
+    char == '/'
+}
+
+pub fn is_asterisk(char: char) -> bool {
+    char == '*'
+}
+
+pub fn is_newline(char: char) -> bool {
+    char == '\n'
+}
+
+pub fn is_carriage_return(char: char) -> bool {
+    char == '\r'
+}
+
 pub fn is_semicolon(char: char) -> bool {
     char == ';'
 }
 
+pub fn is_comma(char: char) -> bool {
+    char == ','
+}
+
+pub fn is_colon(char: char) -> bool {
+    char == ':'
+}
+
+pub fn is_dot(char: char) -> bool {
+    char == '.'
+}
+
+pub fn is_dollar_sign(char: char) -> bool {
+    char == '$'
+}
+
+pub fn is_at_sign(char: char) -> bool {
+    char == '@'
+}
+
+pub fn is_question_mark(char: char) -> bool {
+    char == '?'
+}
+
+pub fn is_delimiter(char: char) -> bool {
+    matches!(char, '(' | ')' | '{' | '}' | '[' | ']')
+}
+
 pub fn is_whitespace(char: char) -> bool {
     char.is_whitespace()
 }
 
+pub fn is_identifier_start(char: char) -> bool {
+    is_letter(char) || char == '_'
+}
+
 pub fn is_in_identifier(char: char) -> bool {
-    char.is_ascii_alphanumeric() || char == '_'
+    char.is_alphanumeric() || char == '_'
+}
+
+// marks like accents that combine with the character before them
+#[cfg(feature = "unicode-normalization")]
+pub fn is_combining_mark(char: char) -> bool {
+    unicode_normalization::char::is_combining_mark(char)
+}
+
+// checks if the character can start a token,
+// the characters that can't are invalid
+pub fn is_token_start(char: char) -> bool {
+    is_digit(char)
+        || is_identifier_start(char)
+        || is_single_quote(char)
+        || is_double_quote(char)
+        || is_backtick(char)
+        || is_operator(char)
+        || is_semicolon(char)
+        || is_comma(char)
+        || is_colon(char)
+        || is_dot(char)
+        || is_question_mark(char)
+        || is_delimiter(char)
+        || is_whitespace(char)
 }
@@ -1,48 +1,131 @@
-#[derive(Debug, PartialEq)]
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
     pub length: usize,
     pub start: usize,
+    // 1-based line and column of the start of the span, they're
+    // only set when the lexer tracks positions
+    pub line: Option<usize>,
+    pub column: Option<usize>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
+    // the token was cut by the end of the input, like
+    // a string without its closing quote
+    pub incomplete: bool,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum TokenKind {
     // NOTE: consider refactoring to add concrete tokens
     // instead of nesting information about the token in
     // its enum value
     String(StringKind),
+    // a single quoted character, when char literals are enabled
+    Char,
     Operator(OperatorKind),
-    Keyword,
+    Keyword(KeywordKind),
     Number,
+    Boolean,
+    Null,
     Identifier,
     Whitespace,
+    // a line break, when newlines are lexed apart from whitespace
+    Newline,
     Semicolon,
+    Comma,
+    Colon,
+    Dot,
+    // a lone `?`, like the one of a ternary
+    Question,
+    OpenParen,
+    CloseParen,
+    OpenBrace,
+    CloseBrace,
+    OpenBracket,
+    CloseBracket,
+    Comment,
+    // holds the maximum nesting depth reached in the comment
+    BlockComment { max_depth: usize },
     Invalid,
+    // zero length token at the end of the input
+    Eof,
+    // the `#!` line a script can start with
+    Shebang,
+    // an `@` followed by a name, like `@override`,
+    // when attributes are enabled
+    Attribute,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeywordKind {
+    Let,
+    Const,
+    If,
+    Else,
+    While,
+    For,
+    Function,
+    Mmk,
+    // a keyword that isn't one of the default ones,
+    // only its category is known
+    Custom(KeywordCategory),
 }
 
-#[derive(Debug, PartialEq)]
+// what a keyword is used for
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeywordCategory {
+    // let, const
+    Declaration,
+    // if, else
+    ControlFlow,
+    // while, for
+    Loop,
+    // function
+    Function,
+    Other,
+}
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum StringKind {
     SingleQuoted,
     DoubleQuoted,
+    // `text`
+    Backtick,
+    // r"text" or r#"text"#, backslashes are literal
+    Raw,
+    // delimited by a pair registered in the options, like «text»
+    Custom,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum OperatorKind {
     // +
     Add,
-    Substract,
+    #[cfg_attr(feature = "serde", serde(alias = "Substract"))]
+    Subtract,
     Multiply,
     Divide,
     Modulo,
+    // **
+    Power,
 
     // +=
     CompoundAdd,
-    CompoundSubstract,
+    #[cfg_attr(feature = "serde", serde(alias = "CompoundSubstract"))]
+    CompoundSubtract,
     CompoundMultiply,
     CompoundDivide,
     CompoundModulo,
@@ -56,6 +139,10 @@ pub enum OperatorKind {
     Equal,
     NotEqual,
 
+    // ===
+    StrictEqual,
+    StrictNotEqual,
+
     // !
     Not,
 
@@ -63,13 +150,293 @@ pub enum OperatorKind {
     GreaterThan,
     LessThan,
 
+    // >=
+    GreaterThanOrEqual,
+    LessThanOrEqual,
+
+    // &&
+    LogicalAnd,
+    LogicalOr,
+
+    // &
+    BitwiseAnd,
+    BitwiseOr,
+    BitwiseXor,
+    BitwiseNot,
+
+    // <<
+    ShiftLeft,
+    ShiftRight,
+    // >>>
+    UnsignedShiftRight,
+
+    // <<=
+    CompoundShiftLeft,
+    CompoundShiftRight,
+
+    // =>
+    FatArrow,
+    ThinArrow,
+
+    // ??
+    NullishCoalescing,
+    // ?.
+    OptionalChaining,
+
+    // ..
+    Range,
+    // ...
+    RangeInclusive,
+
     // Invalid operator
     Invalid,
 }
 
 impl Span {
     pub fn new(start: usize, length: usize) -> Self {
-        Self { start, length }
+        Self {
+            start,
+            length,
+            line: None,
+            column: None,
+        }
+    }
+
+    /**
+     * Gets the 1-based line and column of the start of the span
+     * in the source it was created from
+     * The column is counted in characters, not in bytes
+     */
+    pub fn line_col(&self, source: &str) -> (usize, usize) {
+        let before = &source[..self.start];
+        let line_start = before
+            .rfind(['\n', '\r'])
+            .map_or(0, |newline_index| newline_index + 1);
+
+        // `\r\n` is a single line break, and a lone `\r` is one too
+        let line_break_count = before.matches('\n').count() + before.matches('\r').count()
+            - before.matches("\r\n").count();
+        let line = line_break_count + 1;
+        let column = before[line_start..].chars().count() + 1;
+
+        (line, column)
+    }
+
+    /**
+     * Gets the length of the span in characters, the stored
+     * length is in bytes so it's larger for multibyte characters
+     */
+    pub fn char_length(&self, source: &str) -> usize {
+        source[self.start..self.end()].chars().count()
+    }
+
+    /**
+     * Gets the byte index following the span, it's exclusive
+     * like the end of a range
+     */
+    pub fn end(&self) -> usize {
+        self.start + self.length
+    }
+
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end()
+    }
+
+    /**
+     * Creates the span covering both spans and the gap between them,
+     * it keeps the line and column of the one starting first
+     */
+    pub fn merge(&self, other: &Span) -> Span {
+        let (first, second) = if self.start <= other.start {
+            (self, other)
+        } else {
+            (other, self)
+        };
+        let end = first.end().max(second.end());
+
+        Span {
+            length: end - first.start,
+            ..*first
+        }
+    }
+}
+
+impl Token {
+    /**
+     * Gets the slice of the source the token was lexed from,
+     * spans are in bytes so multibyte characters are sliced correctly
+     */
+    pub fn text<'s>(&self, source: &'s str) -> &'s str {
+        &source[self.span.start..self.span.end()]
+    }
+}
+
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TokenKind::String(string_kind) => write!(f, "string ({string_kind})"),
+            TokenKind::Operator(operator_kind) => write!(f, "operator `{operator_kind}`"),
+            TokenKind::Keyword(keyword_kind) => write!(f, "keyword ({keyword_kind})"),
+            TokenKind::Char => write!(f, "char"),
+            TokenKind::Number => write!(f, "number"),
+            TokenKind::Boolean => write!(f, "boolean"),
+            TokenKind::Null => write!(f, "null"),
+            TokenKind::Identifier => write!(f, "identifier"),
+            TokenKind::Whitespace => write!(f, "whitespace"),
+            TokenKind::Semicolon => write!(f, "`;`"),
+            TokenKind::Comma => write!(f, "`,`"),
+            TokenKind::Colon => write!(f, "`:`"),
+            TokenKind::Dot => write!(f, "`.`"),
+            TokenKind::Question => write!(f, "`?`"),
+            TokenKind::OpenParen => write!(f, "`(`"),
+            TokenKind::CloseParen => write!(f, "`)`"),
+            TokenKind::OpenBrace => write!(f, "`{{`"),
+            TokenKind::CloseBrace => write!(f, "`}}`"),
+            TokenKind::OpenBracket => write!(f, "`[`"),
+            TokenKind::CloseBracket => write!(f, "`]`"),
+            TokenKind::Comment => write!(f, "comment"),
+            TokenKind::BlockComment { .. } => write!(f, "block comment"),
+            TokenKind::Invalid => write!(f, "invalid token"),
+            TokenKind::Eof => write!(f, "end of input"),
+            TokenKind::Shebang => write!(f, "shebang"),
+            TokenKind::Newline => write!(f, "newline"),
+            TokenKind::Attribute => write!(f, "attribute"),
+        }
+    }
+}
+
+impl TokenKind {
+    /**
+     * Checks if the token is trivia, which parsers skip:
+     * whitespace, comments and the shebang line
+     * Newline tokens aren't, since they're only lexed when significant
+     */
+    pub fn is_trivia(&self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace
+                | TokenKind::Comment
+                | TokenKind::BlockComment { .. }
+                | TokenKind::Shebang
+        )
+    }
+}
+
+impl KeywordKind {
+    pub fn category(&self) -> KeywordCategory {
+        match self {
+            KeywordKind::Let | KeywordKind::Const => KeywordCategory::Declaration,
+            KeywordKind::If | KeywordKind::Else => KeywordCategory::ControlFlow,
+            KeywordKind::While | KeywordKind::For => KeywordCategory::Loop,
+            KeywordKind::Function => KeywordCategory::Function,
+            KeywordKind::Mmk => KeywordCategory::Other,
+            KeywordKind::Custom(category) => *category,
+        }
+    }
+}
+
+// the misspelled names the subtraction operators used to have,
+// kept so that code using them still compiles
+#[allow(non_upper_case_globals)]
+impl OperatorKind {
+    #[deprecated(note = "use `OperatorKind::Subtract` instead")]
+    pub const Substract: OperatorKind = OperatorKind::Subtract;
+    #[deprecated(note = "use `OperatorKind::CompoundSubtract` instead")]
+    pub const CompoundSubstract: OperatorKind = OperatorKind::CompoundSubtract;
+}
+
+impl fmt::Display for KeywordKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KeywordKind::Let => "let",
+            KeywordKind::Const => "const",
+            KeywordKind::If => "if",
+            KeywordKind::Else => "else",
+            KeywordKind::While => "while",
+            KeywordKind::For => "for",
+            KeywordKind::Function => "function",
+            KeywordKind::Mmk => "mmk",
+            KeywordKind::Custom(category) => return write!(f, "{category}"),
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for KeywordCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            KeywordCategory::Declaration => "declaration",
+            KeywordCategory::ControlFlow => "control flow",
+            KeywordCategory::Loop => "loop",
+            KeywordCategory::Function => "function",
+            KeywordCategory::Other => "other",
+        };
+
+        write!(f, "{name}")
+    }
+}
+
+impl fmt::Display for StringKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StringKind::SingleQuoted => write!(f, "single-quoted"),
+            StringKind::DoubleQuoted => write!(f, "double-quoted"),
+            StringKind::Backtick => write!(f, "backtick"),
+            StringKind::Raw => write!(f, "raw"),
+            StringKind::Custom => write!(f, "custom"),
+        }
+    }
+}
+
+impl fmt::Display for OperatorKind {
+    // renders the operator as it's written in the source
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let operator = match self {
+            OperatorKind::Add => "+",
+            OperatorKind::Subtract => "-",
+            OperatorKind::Multiply => "*",
+            OperatorKind::Divide => "/",
+            OperatorKind::Modulo => "%",
+            OperatorKind::Power => "**",
+            OperatorKind::CompoundAdd => "+=",
+            OperatorKind::CompoundSubtract => "-=",
+            OperatorKind::CompoundMultiply => "*=",
+            OperatorKind::CompoundDivide => "/=",
+            OperatorKind::CompoundModulo => "%=",
+            OperatorKind::Increment => "++",
+            OperatorKind::Decrement => "--",
+            OperatorKind::DoubleEqual => "==",
+            OperatorKind::Equal => "=",
+            OperatorKind::NotEqual => "!=",
+            OperatorKind::StrictEqual => "===",
+            OperatorKind::StrictNotEqual => "!==",
+            OperatorKind::Not => "!",
+            OperatorKind::GreaterThan => ">",
+            OperatorKind::LessThan => "<",
+            OperatorKind::GreaterThanOrEqual => ">=",
+            OperatorKind::LessThanOrEqual => "<=",
+            OperatorKind::LogicalAnd => "&&",
+            OperatorKind::LogicalOr => "||",
+            OperatorKind::BitwiseAnd => "&",
+            OperatorKind::BitwiseOr => "|",
+            OperatorKind::BitwiseXor => "^",
+            OperatorKind::BitwiseNot => "~",
+            OperatorKind::ShiftLeft => "<<",
+            OperatorKind::ShiftRight => ">>",
+            OperatorKind::UnsignedShiftRight => ">>>",
+            OperatorKind::CompoundShiftLeft => "<<=",
+            OperatorKind::CompoundShiftRight => ">>=",
+            OperatorKind::FatArrow => "=>",
+            OperatorKind::ThinArrow => "->",
+            OperatorKind::NullishCoalescing => "??",
+            OperatorKind::OptionalChaining => "?.",
+            OperatorKind::Range => "..",
+            OperatorKind::RangeInclusive => "...",
+            OperatorKind::Invalid => "invalid operator",
+        };
+
+        write!(f, "{operator}")
     }
 }
 
@@ -77,28 +444,81 @@ pub fn create_token(kind: TokenKind, start: usize, length: usize) -> Token {
     Token {
         kind,
         span: Span::new(start, length),
+        incomplete: false,
     }
 }
 
+pub fn default_keywords() -> HashMap<String, KeywordKind> {
+    // mmk is a special keyword, it's my name abbreviated
+    // not sure what to do with it rn
+    [
+        ("let", KeywordKind::Let),
+        ("const", KeywordKind::Const),
+        ("if", KeywordKind::If),
+        ("else", KeywordKind::Else),
+        ("while", KeywordKind::While),
+        ("for", KeywordKind::For),
+        ("function", KeywordKind::Function),
+        ("mmk", KeywordKind::Mmk),
+    ]
+    .into_iter()
+    .map(|(keyword, keyword_kind)| (String::from(keyword), keyword_kind))
+    .collect()
+}
+
 pub fn match_operator_slice_to_operator_kind(operator: &str) -> OperatorKind {
     match operator {
         // can be a simple operator
         "+" => OperatorKind::Add,
-        "-" => OperatorKind::Substract,
+        "-" => OperatorKind::Subtract,
         "*" => OperatorKind::Multiply,
         "/" => OperatorKind::Divide,
         "=" => OperatorKind::Equal,
         "%" => OperatorKind::Modulo,
+        "**" => OperatorKind::Power,
 
         // can be a comparison operator
         "!=" => OperatorKind::NotEqual,
         "!" => OperatorKind::Not,
         ">" => OperatorKind::GreaterThan,
         "<" => OperatorKind::LessThan,
+        ">=" => OperatorKind::GreaterThanOrEqual,
+        "<=" => OperatorKind::LessThanOrEqual,
+
+        // can be a logical operator
+        "&&" => OperatorKind::LogicalAnd,
+        "||" => OperatorKind::LogicalOr,
+
+        // can be a bitwise operator
+        "&" => OperatorKind::BitwiseAnd,
+        "|" => OperatorKind::BitwiseOr,
+        "^" => OperatorKind::BitwiseXor,
+        "~" => OperatorKind::BitwiseNot,
+        "<<" => OperatorKind::ShiftLeft,
+        ">>" => OperatorKind::ShiftRight,
+
+        // can be a 3 characters long operator
+        "===" => OperatorKind::StrictEqual,
+        "!==" => OperatorKind::StrictNotEqual,
+        ">>>" => OperatorKind::UnsignedShiftRight,
+        "<<=" => OperatorKind::CompoundShiftLeft,
+        ">>=" => OperatorKind::CompoundShiftRight,
+
+        // can be an arrow
+        "=>" => OperatorKind::FatArrow,
+        "->" => OperatorKind::ThinArrow,
+
+        // can start with a question mark
+        "??" => OperatorKind::NullishCoalescing,
+        "?." => OperatorKind::OptionalChaining,
+
+        // can be a range
+        ".." => OperatorKind::Range,
+        "..." => OperatorKind::RangeInclusive,
 
         // can be a compound operator
         "+=" => OperatorKind::CompoundAdd,
-        "-=" => OperatorKind::CompoundSubstract,
+        "-=" => OperatorKind::CompoundSubtract,
         "*=" => OperatorKind::CompoundMultiply,
         "/=" => OperatorKind::CompoundDivide,
         "%=" => OperatorKind::CompoundModulo,
@@ -112,3 +532,143 @@ pub fn match_operator_slice_to_operator_kind(operator: &str) -> OperatorKind {
         _ => OperatorKind::Invalid,
     }
 }
+
+/**
+ * Splits the longest valid operator off the start of the operator,
+ * and returns its kind with the rest of the operator
+ * If no prefix is a valid operator, the first character
+ * is split off as an invalid operator
+ * The rest is split the same way, so a chain like `%%` gives two
+ * modulos, and `%=%=` gives two compound modulos
+ */
+pub fn split_compound_operator(operator: &str) -> (OperatorKind, &str) {
+    // slicing on character boundaries, in case operators
+    // ever contain non ascii characters
+    let prefix = operator
+        .char_indices()
+        .map(|(byte_index, character)| byte_index + character.len_utf8())
+        .rev()
+        .find_map(|length| {
+            let kind = match_operator_slice_to_operator_kind(&operator[..length]);
+            (kind != OperatorKind::Invalid).then_some((kind, length))
+        });
+
+    match prefix {
+        Some((kind, length)) => (kind, &operator[length..]),
+        None => {
+            let first_character_length = operator.chars().next().map_or(0, char::len_utf8);
+            (OperatorKind::Invalid, &operator[first_character_length..])
+        }
+    }
+}
+
+pub fn match_delimiter_to_token_kind(delimiter: char) -> TokenKind {
+    match delimiter {
+        '(' => TokenKind::OpenParen,
+        ')' => TokenKind::CloseParen,
+        '{' => TokenKind::OpenBrace,
+        '}' => TokenKind::CloseBrace,
+        '[' => TokenKind::OpenBracket,
+        ']' => TokenKind::CloseBracket,
+        _ => unreachable!("`{delimiter}` is not a delimiter"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use similar_asserts::assert_eq;
+
+    #[test]
+    fn it_splits_compound_operators_correctly() {
+        assert_eq!(split_compound_operator("=+"), (OperatorKind::Equal, "+"));
+        assert_eq!(
+            split_compound_operator("%=+"),
+            (OperatorKind::CompoundModulo, "+")
+        );
+        assert_eq!(
+            split_compound_operator("++++"),
+            (OperatorKind::Increment, "++")
+        );
+        assert_eq!(split_compound_operator("++"), (OperatorKind::Increment, ""));
+    }
+
+    #[test]
+    fn it_splits_off_the_first_character_when_no_prefix_is_an_operator() {
+        assert_eq!(split_compound_operator("@+"), (OperatorKind::Invalid, "+"));
+        assert_eq!(split_compound_operator("€"), (OperatorKind::Invalid, ""));
+        assert_eq!(split_compound_operator(""), (OperatorKind::Invalid, ""));
+    }
+
+    #[test]
+    fn it_splits_modulo_chains_correctly() {
+        let operators_and_splits = [
+            ("%%", (OperatorKind::Modulo, "%")),
+            ("%=%=", (OperatorKind::CompoundModulo, "%=")),
+            ("%=%", (OperatorKind::CompoundModulo, "%")),
+            ("%%=", (OperatorKind::Modulo, "%=")),
+        ];
+
+        for (operator, split) in operators_and_splits {
+            assert_eq!(split_compound_operator(operator), split, "{operator}");
+        }
+    }
+
+    #[test]
+    fn it_computes_the_end_of_spans_correctly() {
+        assert_eq!(Span::new(3, 4).end(), 7);
+        assert_eq!(Span::new(3, 0).end(), 3);
+    }
+
+    #[test]
+    fn it_checks_if_spans_contain_an_offset_correctly() {
+        let span = Span::new(3, 4);
+
+        assert!(!span.contains(2));
+        assert!(span.contains(3));
+        assert!(span.contains(6));
+        assert!(!span.contains(7));
+        assert!(!Span::new(3, 0).contains(3));
+    }
+
+    #[test]
+    fn it_merges_spans_correctly() {
+        let spans_and_merges = [
+            // adjacent
+            (Span::new(0, 3), Span::new(3, 2), Span::new(0, 5)),
+            // overlapping
+            (Span::new(2, 4), Span::new(0, 3), Span::new(0, 6)),
+            // with a gap
+            (Span::new(0, 1), Span::new(5, 1), Span::new(0, 6)),
+            // one inside the other
+            (Span::new(0, 10), Span::new(2, 3), Span::new(0, 10)),
+        ];
+
+        for (span, other, merged) in spans_and_merges {
+            assert_eq!(span.merge(&other), merged);
+            assert_eq!(other.merge(&span), merged);
+        }
+    }
+
+    #[test]
+    fn it_matches_the_subtraction_operators_correctly() {
+        assert_eq!(
+            match_operator_slice_to_operator_kind("-"),
+            OperatorKind::Subtract
+        );
+        assert_eq!(
+            match_operator_slice_to_operator_kind("-="),
+            OperatorKind::CompoundSubtract
+        );
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn it_keeps_the_misspelled_subtraction_operators_as_aliases() {
+        assert_eq!(OperatorKind::Substract, OperatorKind::Subtract);
+        assert_eq!(
+            OperatorKind::CompoundSubstract,
+            OperatorKind::CompoundSubtract
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use super::token::*;
+
+pub(super) fn is_significant(token: &Token) -> bool {
+    !token.kind.is_trivia()
+}
+
+// number of tokens of each family, errors are the invalid tokens
+#[derive(Debug, PartialEq, Default)]
+pub struct TokenStats {
+    pub keywords: usize,
+    pub identifiers: usize,
+    pub numbers: usize,
+    pub strings: usize,
+    pub operators: usize,
+    pub errors: usize,
+}
+
+pub fn token_stats(tokens: &[Token]) -> TokenStats {
+    let mut stats = TokenStats::default();
+
+    for token in tokens {
+        match token.kind {
+            TokenKind::Keyword(_) => stats.keywords += 1,
+            TokenKind::Identifier => stats.identifiers += 1,
+            TokenKind::Number => stats.numbers += 1,
+            TokenKind::String(_) => stats.strings += 1,
+            TokenKind::Operator(_) => stats.operators += 1,
+            TokenKind::Invalid => stats.errors += 1,
+            _ => {}
+        }
+    }
+
+    stats
+}
+
+/**
+ * Gets the tokens of the given kind, the kinds holding a value
+ * like operators only match the ones with the same value
+ */
+pub fn tokens_of_kind<'t>(
+    tokens: &'t [Token],
+    kind: &'t TokenKind,
+) -> impl Iterator<Item = &'t Token> {
+    tokens.iter().filter(move |token| token.kind == *kind)
+}
+
+/**
+ * Gets the token containing the byte offset, like the token under
+ * the cursor of an editor, the tokens must be sorted by their start
+ * Offsets past the last token, or in the gaps left by skipped
+ * whitespace or comments, aren't in any token
+ */
+pub fn token_at(tokens: &[Token], byte_offset: usize) -> Option<&Token> {
+    let index = tokens.partition_point(|token| token.span.end() <= byte_offset);
+
+    tokens
+        .get(index)
+        .filter(|token| token.span.contains(byte_offset))
+}
+
+/**
+ * Splits the tokens after each semicolon, the semicolon
+ * being the last token of its statement
+ * Trailing tokens that aren't terminated by a semicolon
+ * make up the last statement
+ */
+pub fn split_statements(tokens: &[Token]) -> Vec<&[Token]> {
+    tokens
+        .split_inclusive(|token| token.kind == TokenKind::Semicolon)
+        .collect()
+}
+
+/**
+ * Gets the span covering the significant tokens, from the start
+ * of the first one to the end of the last one
+ */
+pub fn span_of(tokens: &[Token]) -> Option<Span> {
+    let first = tokens.iter().find(|token| is_significant(token))?;
+    let last = tokens.iter().rfind(|token| is_significant(token))?;
+
+    Some(first.span.merge(&last.span))
+}
+
+/**
+ * Gets the span of each semicolon terminated statement,
+ * which is useful to build an outline of the source
+ */
+pub fn statement_spans(tokens: &[Token]) -> Vec<Span> {
+    split_statements(tokens)
+        .into_iter()
+        .filter(|statement| {
+            statement
+                .last()
+                .is_some_and(|token| token.kind == TokenKind::Semicolon)
+        })
+        .filter_map(span_of)
+        .collect()
+}
+
+/**
+ * Rebuilds the source from the text of the tokens, lexing
+ * leaves no gaps so it's the original source, unless
+ * whitespace, comments or invalid tokens were skipped
+ */
+pub fn to_source(tokens: &[Token], original: &str) -> String {
+    tokens.iter().map(|token| token.text(original)).collect()
+}
+
+/**
+ * Creates a copy of the tokens with the start of each span
+ * remapped, which is useful to compare token streams of
+ * sources that only differ by their layout
+ */
+pub fn remap_spans(tokens: &[Token], mapping: impl Fn(usize) -> usize) -> Vec<Token> {
+    tokens
+        .iter()
+        .map(|token| Token {
+            kind: token.kind.clone(),
+            span: Span::new(mapping(token.span.start), token.span.length),
+            incomplete: token.incomplete,
+        })
+        .collect()
+}
+
+/**
+ * Checks that two token streams have the same significant tokens,
+ * by kind and by text, ignoring their spans and the trivia between
+ * them, which confirms that reformatting a source didn't change it
+ * The sources are needed since tokens don't carry their text
+ */
+pub fn structurally_equal(a: &[Token], a_source: &str, b: &[Token], b_source: &str) -> bool {
+    fn significant_tokens<'t>(
+        tokens: &'t [Token],
+        source: &'t str,
+    ) -> impl Iterator<Item = (&'t TokenKind, &'t str)> {
+        tokens
+            .iter()
+            .filter(|token| is_significant(token))
+            .map(|token| {
+                let text = token.text(source);
+                (&token.kind, text)
+            })
+    }
+
+    significant_tokens(a, a_source).eq(significant_tokens(b, b_source))
+}
+
+/**
+ * Gets the spans of the strings that aren't quoted like the
+ * first string of their statement, for linting consistent quoting
+ */
+pub fn mixed_quotes_in_expression(tokens: &[Token]) -> Vec<Span> {
+    let mut spans = Vec::new();
+
+    for statement in split_statements(tokens) {
+        let mut first_string_kind = None;
+
+        for token in statement {
+            if let TokenKind::String(string_kind) = &token.kind {
+                match first_string_kind {
+                    None => first_string_kind = Some(string_kind),
+                    Some(first_string_kind) if first_string_kind != string_kind => {
+                        spans.push(token.span);
+                    }
+                    Some(_) => {}
+                }
+            }
+        }
+    }
+
+    spans
+}
@@ -0,0 +1,4 @@
+#![feature(test)]
+extern crate test;
+
+pub mod lexer;
@@ -0,0 +1,73 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+fn lexer_command() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_simple-lexer"))
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"))
+}
+
+#[test]
+fn it_prints_the_tokens_of_a_file() {
+    let output = lexer_command().arg(fixture("valid.txt")).output().unwrap();
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = stdout.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 18);
+    assert_eq!(lines[0], "0..3 keyword (let) \"let\"");
+    assert_eq!(lines[2], "4..9 identifier \"value\"");
+    assert_eq!(lines[6], "12..13 number \"1\"");
+    assert_eq!(lines[16], "22..23 whitespace \"\\n\"");
+    assert_eq!(lines[17], "17 tokens, 0 errors");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn it_reports_errors_with_a_failure_exit_code() {
+    let output = lexer_command()
+        .arg(fixture("invalid.txt"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().last(), Some("10 tokens, 1 error"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("error: invalid operator at 1:11\n"));
+    assert!(stderr.contains("1 | let value =+ 1;"));
+}
+
+#[test]
+fn it_reads_the_source_from_stdin_without_arguments() {
+    let mut child = lexer_command()
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(b"x;").unwrap();
+
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "0..1 identifier \"x\"\n1..2 `;` \";\"\n2 tokens, 0 errors\n"
+    );
+}
+
+#[test]
+fn it_fails_on_a_missing_file() {
+    let output = lexer_command()
+        .arg(fixture("missing.txt"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .starts_with("error: can't read"));
+}